@@ -0,0 +1,83 @@
+// MIT License
+
+/*Copyright (c) 2024 Based Labs
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
+
+use async_trait::async_trait;
+use uuid::Uuid;
+use crate::api::ollama::{BatchOptions, PlanOptions};
+use crate::models::types::{
+    CellContext, DimensionalAdjustment, DimensionalPosition, Plan, RealTimeContext, Thought,
+};
+
+// The operations the colony needs from a model backend. OllamaClient is the
+// main implementation; anything that can answer these (an OpenAI-compatible
+// endpoint, a test double) can be dropped in instead.
+#[async_trait]
+pub trait LanguageModel: Send + Sync {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    async fn generate(&self, prompt: &str) -> Result<String, Self::Error>;
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, Self::Error>;
+
+    async fn generate_contextual_thought(
+        &self,
+        context: &CellContext,
+        real_time_context: &RealTimeContext,
+        mission: &str,
+    ) -> Result<(String, f64, Vec<String>), Self::Error>;
+
+    // One entry per cell, in the order the cells were given: its thoughts, or
+    // the error that left it with none
+    async fn generate_contextual_thoughts_batch(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Vec<(Uuid, Result<Vec<(String, f64, Vec<String>)>, Self::Error>)>;
+
+    // Stops at the first error. Entries are in the order the cells were given.
+    async fn generate_contextual_thoughts_batch_strict(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Result<Vec<(Uuid, Vec<(String, f64, Vec<String>)>)>, Self::Error>;
+
+    async fn gather_real_time_context(
+        &self,
+        recent_thoughts: Option<Vec<String>>,
+    ) -> Result<RealTimeContext, Self::Error>;
+
+    async fn evaluate_dimensional_state(
+        &self,
+        position: &DimensionalPosition,
+        recent_thoughts: &[Thought],
+        recent_plans: &[Plan],
+    ) -> Result<DimensionalAdjustment, Self::Error>;
+
+    async fn create_plan_with(
+        &self,
+        thoughts: &[Thought],
+        options: &PlanOptions,
+    ) -> Result<Plan, Self::Error>;
+
+    async fn create_plan(&self, thoughts: &[Thought]) -> Result<Plan, Self::Error> {
+        self.create_plan_with(thoughts, &PlanOptions::default()).await
+    }
+
+    async fn compress_memories(&self, memories: &[String]) -> Result<String, Self::Error>;
+
+    // Rates an existing thought's relevance to the cell, in 0.0..=1.0
+    async fn score_thought(&self, thought: &str, context: &CellContext) -> Result<f64, Self::Error>;
+}
@@ -0,0 +1,295 @@
+// MIT License
+
+/*Copyright (c) 2024 Based Labs
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use async_trait::async_trait;
+use uuid::Uuid;
+use crate::api::language_model::LanguageModel;
+use crate::api::ollama::{BatchOptions, PlanOptions};
+use crate::models::types::{
+    CellContext, DimensionalAdjustment, DimensionalPosition, Plan, RealTimeContext, Thought,
+};
+
+// Test double for LanguageModel. Canned responses are handed out in the
+// order they were queued and every call is recorded for later assertions.
+// A call with nothing queued fails with MockError instead of inventing output,
+// except for the context and dimensional calls, which have neutral defaults.
+#[derive(Default)]
+pub struct MockLanguageModel {
+    responses: Mutex<VecDeque<String>>,
+    thoughts: Mutex<VecDeque<(String, f64, Vec<String>)>>,
+    plans: Mutex<VecDeque<Plan>>,
+    embeddings: Mutex<VecDeque<Vec<f32>>>,
+    contexts: Mutex<VecDeque<RealTimeContext>>,
+    adjustments: Mutex<VecDeque<DimensionalAdjustment>>,
+    scores: Mutex<VecDeque<f64>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockCall {
+    Generate(String),
+    Embed(String),
+    ContextualThought { mission: String },
+    RealTimeContext(Option<Vec<String>>),
+    EvaluateDimensionalState,
+    CreatePlan(Vec<String>),
+    CompressMemories(Vec<String>),
+    ScoreThought(String),
+}
+
+#[derive(Debug)]
+pub struct MockError(pub String);
+
+impl std::error::Error for MockError {}
+
+impl std::fmt::Display for MockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mock language model: {}", self.0)
+    }
+}
+
+impl MockLanguageModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_response(self, response: impl Into<String>) -> Self {
+        self.responses.lock().unwrap().push_back(response.into());
+        self
+    }
+
+    pub fn with_thought(self, content: impl Into<String>, relevance: f64, factors: Vec<String>) -> Self {
+        self.thoughts.lock().unwrap().push_back((content.into(), relevance, factors));
+        self
+    }
+
+    pub fn with_plan(self, plan: Plan) -> Self {
+        self.plans.lock().unwrap().push_back(plan);
+        self
+    }
+
+    pub fn with_embedding(self, embedding: Vec<f32>) -> Self {
+        self.embeddings.lock().unwrap().push_back(embedding);
+        self
+    }
+
+    pub fn with_real_time_context(self, context: RealTimeContext) -> Self {
+        self.contexts.lock().unwrap().push_back(context);
+        self
+    }
+
+    pub fn with_adjustment(self, adjustment: DimensionalAdjustment) -> Self {
+        self.adjustments.lock().unwrap().push_back(adjustment);
+        self
+    }
+
+    pub fn with_score(self, score: f64) -> Self {
+        self.scores.lock().unwrap().push_back(score);
+        self
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: MockCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+fn next<T>(queue: &Mutex<VecDeque<T>>, what: &str) -> Result<T, MockError> {
+    queue.lock()
+        .unwrap()
+        .pop_front()
+        .ok_or_else(|| MockError(format!("no canned {} left", what)))
+}
+
+#[async_trait]
+impl LanguageModel for MockLanguageModel {
+    type Error = MockError;
+
+    async fn generate(&self, prompt: &str) -> Result<String, MockError> {
+        self.record(MockCall::Generate(prompt.to_string()));
+        next(&self.responses, "response")
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, MockError> {
+        self.record(MockCall::Embed(text.to_string()));
+        next(&self.embeddings, "embedding")
+    }
+
+    async fn generate_contextual_thought(
+        &self,
+        _context: &CellContext,
+        _real_time_context: &RealTimeContext,
+        mission: &str,
+    ) -> Result<(String, f64, Vec<String>), MockError> {
+        self.record(MockCall::ContextualThought { mission: mission.to_string() });
+        next(&self.thoughts, "thought")
+    }
+
+    async fn generate_contextual_thoughts_batch(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        _additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Vec<(Uuid, Result<Vec<(String, f64, Vec<String>)>, MockError>)> {
+        let mut results = Vec::new();
+        for (cell_id, context) in cell_contexts {
+            let mut thoughts = Vec::new();
+            let mut failure = None;
+            for _ in 0..options.thoughts_per_cell.max(1) {
+                match self.generate_contextual_thought(context, real_time_context, mission).await {
+                    Ok(thought) => thoughts.push(thought),
+                    Err(e) => failure = Some(e),
+                }
+            }
+            let outcome = match failure {
+                Some(e) if thoughts.is_empty() => Err(e),
+                _ => Ok(thoughts),
+            };
+            results.push((*cell_id, outcome));
+        }
+        results
+    }
+
+    async fn generate_contextual_thoughts_batch_strict(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        _additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Result<Vec<(Uuid, Vec<(String, f64, Vec<String>)>)>, MockError> {
+        let mut results = Vec::new();
+        for (cell_id, context) in cell_contexts {
+            let mut thoughts = Vec::new();
+            for _ in 0..options.thoughts_per_cell.max(1) {
+                thoughts.push(self.generate_contextual_thought(context, real_time_context, mission).await?);
+            }
+            results.push((*cell_id, thoughts));
+        }
+        Ok(results)
+    }
+
+    async fn gather_real_time_context(
+        &self,
+        recent_thoughts: Option<Vec<String>>,
+    ) -> Result<RealTimeContext, MockError> {
+        self.record(MockCall::RealTimeContext(recent_thoughts));
+        Ok(self.contexts.lock().unwrap().pop_front().unwrap_or_default())
+    }
+
+    async fn evaluate_dimensional_state(
+        &self,
+        _position: &DimensionalPosition,
+        _recent_thoughts: &[Thought],
+        _recent_plans: &[Plan],
+    ) -> Result<DimensionalAdjustment, MockError> {
+        self.record(MockCall::EvaluateDimensionalState);
+        Ok(self.adjustments.lock().unwrap().pop_front().unwrap_or(DimensionalAdjustment {
+            energy: 0.0,
+            dopamine: 0.0,
+            raw_response: String::new(),
+        }))
+    }
+
+    async fn create_plan_with(
+        &self,
+        thoughts: &[Thought],
+        _options: &PlanOptions,
+    ) -> Result<Plan, MockError> {
+        self.record(MockCall::CreatePlan(thoughts.iter().map(|t| t.content.clone()).collect()));
+        next(&self.plans, "plan")
+    }
+
+    async fn compress_memories(&self, memories: &[String]) -> Result<String, MockError> {
+        self.record(MockCall::CompressMemories(memories.to_vec()));
+        next(&self.responses, "response")
+    }
+
+    async fn score_thought(&self, thought: &str, _context: &CellContext) -> Result<f64, MockError> {
+        self.record(MockCall::ScoreThought(thought.to_string()));
+        next(&self.scores, "score")
+    }
+}
+
+// A cell at the origin of every dimension, shared by the client tests
+#[cfg(test)]
+pub fn cell_context(energy_level: f64) -> CellContext {
+    CellContext {
+        current_focus: "coordination".to_string(),
+        active_research_topics: Vec::new(),
+        recent_discoveries: Vec::new(),
+        collaboration_history: Vec::new(),
+        performance_metrics: HashMap::new(),
+        evolution_stage: 1,
+        energy_level,
+        dimensional_position: DimensionalPosition::from_dimensions([0.0; 6]),
+        dopamine: 0.5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::models::types::{PlanStatus, Score};
+
+    fn thought(content: &str) -> Thought {
+        Thought::from_generation(content, 0.5, Vec::new())
+    }
+
+    #[tokio::test]
+    async fn test_create_plan_records_thoughts_and_returns_canned_plan() {
+        let canned = Plan {
+            id: Uuid::new_v4(),
+            thoughts: Vec::new(),
+            nodes: Vec::new(),
+            summary: "Canned plan".to_string(),
+            score: Score::from(0.9),
+            participating_cells: Vec::new(),
+            created_at: Utc::now(),
+            status: PlanStatus::Proposed,
+        };
+        let model = MockLanguageModel::new().with_plan(canned.clone());
+
+        let plan = model.create_plan(&[thought("first"), thought("second")]).await.unwrap();
+
+        assert_eq!(plan.id, canned.id);
+        assert_eq!(
+            model.calls(),
+            vec![MockCall::CreatePlan(vec!["first".to_string(), "second".to_string()])]
+        );
+        assert!(model.create_plan(&[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batch_keeps_thoughts_of_cells_that_succeeded() {
+        let context = cell_context(50.0);
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let cells = [(first, &context), (second, &context)];
+        let rtc = RealTimeContext::default();
+
+        let model = MockLanguageModel::new().with_thought("only one", 0.7, Vec::new());
+        let results = model.generate_contextual_thoughts_batch(&cells, &rtc, "mission", &[], &BatchOptions::default()).await;
+        assert_eq!(results[0].0, first);
+        assert_eq!(results[0].1.as_ref().unwrap()[0].0, "only one");
+        assert_eq!(results[1].0, second);
+        assert!(results[1].1.is_err());
+
+        let model = MockLanguageModel::new().with_thought("only one", 0.7, Vec::new());
+        assert!(model.generate_contextual_thoughts_batch_strict(&cells, &rtc, "mission", &[], &BatchOptions::default()).await.is_err());
+    }
+}
@@ -1,447 +1,3895 @@
 use std::collections::HashMap;
-use serde::{Deserialize, Serialize};
-use reqwest::Client;
-use crate::models::types::{CellContext, RealTimeContext, Plan, PlanNode, Thought, DimensionalPosition, PlanNodeStatus, PlanStatus};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use reqwest::header::HeaderValue;
+use reqwest::{Client, Method, RequestBuilder};
+use regex::{Regex, RegexBuilder};
+use futures::{future, Stream, StreamExt, TryStreamExt};
+use crate::models::types::{
+    CellContext, RealTimeContext, Plan, PlanNode, Thought, DimensionalPosition, DimensionalAdjustment,
+    EnvValue, Event, PlanError, PlanNodeStatus, PlanStatus, Score,
+};
+use async_trait::async_trait;
+use crate::api::cache::ResponseCache;
+use crate::api::language_model::LanguageModel;
+use crate::api::prompts::{self, PromptTemplates};
+use crate::api::response_parser::{
+    header_matches, split_at_header, strip_header, ParsedPlan, ParserConfig, ResponseFormat, ResponseParser,
+};
+use crate::api::telemetry;
+use crate::api::parsing::{
+    extract_numbers, normalize_text, parse_fraction, parse_json_lenient, strip_code_fences, strip_list_marker,
+};
+use crate::models::constants::{API_TIMEOUT_SECS, DEFAULT_NODE_IMPORTANCE};
+use crate::utils::logging::log_warning;
 use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 use chrono::Utc;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 
-const OLLAMA_API_URL: &str = "http://localhost:11434/api";
+const DEFAULT_OLLAMA_API_URL: &str = "http://localhost:11434/api";
+pub const DEFAULT_MODEL: &str = "llama3.1:8b";
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+const DEFAULT_THOUGHT_ATTEMPTS: usize = 3;
+const LENGTH_TOLERANCE: f64 = 0.25;
+const OTHER_CATEGORY: &str = "other";
+const DEFAULT_MIN_THOUGHT_ENERGY: f64 = 0.0;
+const MAX_KMEANS_ITERATIONS: usize = 50;
+const DEFAULT_USER_AGENT: &str = concat!("localcreature/", env!("CARGO_PKG_VERSION"));
+const REQUEST_ID_HEADER: &str = "X-Request-ID";
+const DEFAULT_MIN_PLAN_NODES: usize = 3;
+const PLAN_STREAM_BUFFER: usize = 16;
+// Sampling temperature for the first of several thoughts per cell; each
+// further thought is sampled a little hotter to spread them out
+const DIVERSE_BASE_TEMPERATURE: f64 = 0.7;
+const DIVERSE_TEMPERATURE_STEP: f64 = 0.15;
+const COMPRESSION_TOLERANCE: f64 = 1.5;
+// Used for plan scores and thought relevance when the model gives no usable number
+const DEFAULT_SCORE: f64 = 0.5;
+// Model downloads can run far longer than a normal request
+const PULL_TIMEOUT_SECS: u64 = 6 * 60 * 60;
+const PING_TIMEOUT_SECS: u64 = 2;
+const DEFAULT_THOUGHT_PERSONA: &str = "You are an AI system focused on developing innovative collaboration approaches.
+Your task is to generate an insightful thought about AI collaboration systems.";
+// Cell state the model tends to echo back into thoughts instead of reasoning about it
+const DEFAULT_PROHIBITED_TERMS: [&str; 9] = [
+    "energy level",
+    "evolution stage",
+    "dimensional position",
+    "emergence:",
+    "coherence:",
+    "resilience:",
+    "intelligence:",
+    "efficiency:",
+    "integration:",
+];
 
+#[derive(Debug)]
+pub enum OllamaError {
+    Http(reqwest::Error),
+    Json(serde_json::Error),
+    ConnectTimeout,
+    RequestTimeout,
+    Cancelled,
+    InvalidUrl(String),
+    InvalidPattern(regex::Error),
+    InvalidConfig(String),
+    ModelNotFound(String),
+    PromptTooLarge { estimated_tokens: usize, limit: usize },
+    PullFailed { model: String, message: String },
+    Server { status: u16, message: String },
+    Parse { context: String },
+    EmptyResponse,
+    InvalidFormat { expected: String, got: String },
+    Plan(PlanError),
+    // Returned instead of calling the model when dry-run mode is on
+    DryRun { prompt: String },
+    VisionUnsupported(String),
+    InsertUnsupported(String),
+    // The cell is too drained to think; no request was made
+    InsufficientEnergy { energy: f64, required: f64 },
+}
+
+impl OllamaError {
+    // Errors worth retrying as-is: the server was unreachable, slow, or failing
+    // internally. Formatting problems need a different prompt, not a retry.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            OllamaError::Http(e) => e.is_connect() || e.is_request(),
+            OllamaError::ConnectTimeout | OllamaError::RequestTimeout => true,
+            // Seen under load; the same request usually succeeds next time
+            OllamaError::EmptyResponse => true,
+            OllamaError::Server { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    // Errors meaning this model can't serve requests right now, as opposed to
+    // the request itself being bad. Worth trying a different model for.
+    pub fn is_model_unavailable(&self) -> bool {
+        match self {
+            OllamaError::ModelNotFound(_) => true,
+            OllamaError::Server { status: 404, .. } => true,
+            OllamaError::Server { status, message } if *status >= 500 => {
+                let message = message.to_lowercase();
+                ["load", "memory", "runner"].iter().any(|hint| message.contains(hint))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Error for OllamaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OllamaError::Http(e) => Some(e),
+            OllamaError::Json(e) => Some(e),
+            OllamaError::InvalidPattern(e) => Some(e),
+            OllamaError::Plan(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for OllamaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OllamaError::Http(e) => write!(f, "HTTP error: {}", e),
+            OllamaError::Json(e) => write!(f, "JSON error: {}", e),
+            OllamaError::ConnectTimeout => write!(f, "Timed out connecting to Ollama"),
+            OllamaError::RequestTimeout => write!(f, "Ollama request timed out"),
+            OllamaError::Cancelled => write!(f, "Ollama request was cancelled"),
+            OllamaError::InvalidUrl(msg) => write!(f, "Invalid Ollama base URL: {}", msg),
+            OllamaError::InvalidPattern(e) => write!(f, "Invalid prohibited-content pattern: {}", e),
+            OllamaError::InvalidConfig(msg) => write!(f, "Invalid Ollama client configuration: {}", msg),
+            OllamaError::ModelNotFound(model) => {
+                write!(f, "Model '{}' is not available on the Ollama server; run `ollama pull {}`", model, model)
+            }
+            OllamaError::PromptTooLarge { estimated_tokens, limit } => write!(
+                f,
+                "Prompt is roughly {} tokens, which exceeds the {} token context window",
+                estimated_tokens, limit
+            ),
+            OllamaError::PullFailed { model, message } => write!(f, "Failed to pull model '{}': {}", model, message),
+            OllamaError::Server { status, message } => write!(f, "Ollama returned {}: {}", status, message),
+            OllamaError::Parse { context } => write!(f, "Failed to parse {}", context),
+            OllamaError::EmptyResponse => write!(f, "Model returned an empty response"),
+            OllamaError::InvalidFormat { expected, got } => {
+                write!(f, "Invalid response format: expected {}, got {:?}", expected, got)
+            }
+            OllamaError::Plan(e) => write!(f, "Plan error: {}", e),
+            OllamaError::DryRun { prompt } => write!(f, "Dry run, prompt not sent:\n{}", prompt),
+            OllamaError::VisionUnsupported(model) => {
+                write!(f, "Model '{}' does not accept images; use a vision model such as llava", model)
+            }
+            OllamaError::InsertUnsupported(model) => {
+                write!(f, "Model '{}' does not support fill-in-the-middle; use a code model such as qwen2.5-coder", model)
+            }
+            OllamaError::InsufficientEnergy { energy, required } => {
+                write!(f, "Cell energy {:.2} is not above the {:.2} needed to generate a thought", energy, required)
+            }
+        }
+    }
+}
+
+impl From<serde_json::Error> for OllamaError {
+    fn from(e: serde_json::Error) -> Self {
+        OllamaError::Json(e)
+    }
+}
+
+impl From<PlanError> for OllamaError {
+    fn from(e: PlanError) -> Self {
+        OllamaError::Plan(e)
+    }
+}
+
+impl From<reqwest::Error> for OllamaError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            if e.is_connect() {
+                OllamaError::ConnectTimeout
+            } else {
+                OllamaError::RequestTimeout
+            }
+        } else {
+            OllamaError::Http(e)
+        }
+    }
+}
+
+// Clones share the underlying connection pool and caches
+#[derive(Clone)]
 pub struct OllamaClient {
     client: Client,
+    base_url: String,
     model: String,
+    embedding_model: Option<String>,
+    use_chat: bool,
+    persona: String,
+    prohibited_terms: Vec<String>,
+    prohibited_patterns: Vec<Regex>,
+    context_window: Option<usize>,
+    cache: Option<Arc<Mutex<ResponseCache>>>,
+    // Embeddings are deterministic for a given model, so they're always
+    // cached. Keyed by (model, text) since clones share the cache but may
+    // embed with different models.
+    embedding_cache: Arc<Mutex<EmbeddingCache>>,
+    keep_alive: Option<KeepAlive>,
+    method_options: HashMap<RequestKind, GenerateOptions>,
+    prompts: PromptTemplates,
+    dry_run: bool,
+    // Shared by clones, so the limit holds across every caller
+    generation_slots: Option<Arc<Semaphore>>,
+    // Tried in order when the primary model can't be loaded
+    fallback_models: Vec<String>,
+    parser: Arc<dyn ResponseParser>,
+    parser_config: ParserConfig,
+    min_thought_energy: f64,
+    // Replaces DEFAULT_USER_AGENT on every request when set
+    user_agent: Option<HeaderValue>,
+}
+
+// (model, text) -> embedding
+type EmbeddingCache = HashMap<(String, String), Vec<f32>>;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::Assistant, content: content.into() }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    pub max_concurrency: usize,
+    pub thoughts_per_cell: usize,
+    // Bounds each thought request; one that runs over fails with RequestTimeout
+    pub cell_timeout: Option<Duration>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrency: DEFAULT_BATCH_CONCURRENCY,
+            thoughts_per_cell: 1,
+            cell_timeout: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ThoughtOptions {
+    pub max_attempts: usize,
+    // Extra lines shown to the model alongside the cell and environment context
+    pub additional_context: Vec<String>,
+    pub sampling: Option<GenerateOptions>,
+    // Thoughts scoring below this are regenerated while attempts remain
+    pub min_relevance: Option<f64>,
+    // Asked for in the prompt; thoughts far outside it are regenerated
+    pub length: Option<ThoughtLength>,
+}
+
+impl Default for ThoughtOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_THOUGHT_ATTEMPTS,
+            additional_context: Vec::new(),
+            sampling: None,
+            min_relevance: None,
+            length: None,
+        }
+    }
+}
+
+// Inclusive bounds on a thought's length. Models rarely hit an exact count,
+// so only thoughts more than LENGTH_TOLERANCE outside the bounds are rejected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThoughtLength {
+    Sentences { min: usize, max: usize },
+    Characters { min: usize, max: usize },
+}
+
+impl ThoughtLength {
+    fn instruction(&self) -> String {
+        match *self {
+            ThoughtLength::Sentences { min, max } if min == max => format!("Write exactly {} sentences", min),
+            ThoughtLength::Sentences { min, max } => format!("Write between {} and {} sentences", min, max),
+            ThoughtLength::Characters { min, max } => format!("Write between {} and {} characters", min, max),
+        }
+    }
+
+    fn measure(&self, text: &str) -> usize {
+        match self {
+            ThoughtLength::Sentences { .. } => count_sentences(text),
+            ThoughtLength::Characters { .. } => text.trim().chars().count(),
+        }
+    }
+
+    pub fn accepts(&self, text: &str) -> bool {
+        let (min, max) = match *self {
+            ThoughtLength::Sentences { min, max } | ThoughtLength::Characters { min, max } => (min, max),
+        };
+        let length = self.measure(text) as f64;
+        length >= (min as f64 * (1.0 - LENGTH_TOLERANCE)).floor()
+            && length <= (max as f64 * (1.0 + LENGTH_TOLERANCE)).ceil()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanAssessment {
+    pub confidence: f64,
+    pub risks: Vec<String>,
+    pub assumptions: Vec<String>,
+}
+
+// Generated text along with the model that actually produced it, which may
+// be a fallback rather than the configured model
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generation {
+    pub text: String,
+    pub model: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneratedThought {
+    pub content: String,
+    pub relevance: f64,
+    pub factors: Vec<String>,
+    pub attempts: usize,
+    // Set when every attempt leaked prohibited content and the offending lines
+    // were stripped from the final one instead
+    pub force_cleaned: bool,
+    // Model output of the final attempt, before parsing
+    pub raw_response: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum CompressionTarget {
+    Words(usize),
+    Ratio(f64),
+}
+
+impl CompressionTarget {
+    fn word_budget(self, input_words: usize) -> usize {
+        match self {
+            CompressionTarget::Words(words) => words.max(1),
+            CompressionTarget::Ratio(ratio) => ((input_words as f64 * ratio).round() as usize).max(1),
+        }
+    }
+}
+
+// Items yielded by create_plan_stream: nodes as they are parsed, then the
+// finished plan
+#[derive(Clone, Debug)]
+pub enum PlanStreamEvent {
+    Node(PlanNode),
+    Plan(Plan),
+}
+
+#[derive(Clone, Debug)]
+pub struct PlanOptions {
+    pub infer_dependencies: bool,
+    // Caps the estimated tokens of thought text put in the prompt, keeping the
+    // most relevant thoughts. Defaults to half the context window when one is set.
+    pub thought_token_budget: Option<usize>,
+    // Parsed plans are padded with generic nodes up to min_nodes and cut back
+    // to their first max_nodes
+    pub min_nodes: usize,
+    pub max_nodes: Option<usize>,
+    // Follows up with rank_plan_nodes to weight each node's importance
+    pub rank_nodes: bool,
+    // A proposed plan hasn't started, so node completions are reset to 0
+    // unless this asks to keep the estimates the model gave
+    pub keep_model_completion: bool,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            infer_dependencies: false,
+            thought_token_budget: None,
+            min_nodes: DEFAULT_MIN_PLAN_NODES,
+            max_nodes: None,
+            rank_nodes: false,
+            keep_model_completion: false,
+        }
+    }
+}
+
+impl PlanOptions {
+    fn node_shape(&self) -> &'static str {
+        if self.infer_dependencies {
+            "{\"title\": \"...\", \"description\": \"...\", \"estimated_completion\": 0.0, \"depends_on\": [1]}"
+        } else {
+            "{\"title\": \"...\", \"description\": \"...\", \"estimated_completion\": 0.0}"
+        }
+    }
+
+    fn node_format(&self) -> &'static str {
+        if self.infer_dependencies {
+            "[Node Title] | [Detailed description of the node's objective and approach] | [Completion estimate between 0-1] | [Numbers of earlier nodes this depends on, or none]"
+        } else {
+            "[Node Title] | [Detailed description of the node's objective and approach] | [Completion estimate between 0-1]"
+        }
+    }
+
+    // How the prompt phrases the node count, e.g. "between 2 and 5"
+    fn node_count(&self) -> String {
+        match self.max_nodes {
+            Some(max) if max <= self.min_nodes => format!("exactly {}", self.min_nodes),
+            Some(max) => format!("between {} and {}", self.min_nodes, max),
+            None => format!("at least {}", self.min_nodes),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct GenerateOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<usize>,
+    // Generation ends as soon as any of these strings is produced
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    // Sends the prompt exactly as given, skipping the model's prompt template.
+    // A request-level flag in Ollama, so it is lifted out of `options`; only
+    // /api/generate honours it.
+    #[serde(skip)]
+    pub raw: bool,
+}
+
+impl GenerateOptions {
+    // Greedy sampling with a fixed seed, for calls that should be repeatable
+    pub fn deterministic(seed: i64) -> Self {
+        Self {
+            temperature: Some(0.0),
+            seed: Some(seed),
+            ..Default::default()
+        }
+    }
+}
+
+// The structured requests the client makes on its own behalf, each with a
+// preset for sampling temperature and how much the model may write. Numeric
+// answers run at temperature 0 so they are repeatable (and cacheable), free
+// text gets progressively more room to vary. Override a preset with
+// OllamaClient::with_method_options; the README lists the built-in values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    DimensionalEvaluation,
+    Thought,
+    Plan,
+    RealTimeContext,
+    Compression,
+    Scoring,
+    FillInMiddle,
+}
+
+impl RequestKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            RequestKind::DimensionalEvaluation => "evaluate_dimensional_state",
+            RequestKind::Thought => "thought",
+            RequestKind::Plan => "create_plan",
+            RequestKind::RealTimeContext => "gather_real_time_context",
+            RequestKind::Compression => "compress_memories",
+            RequestKind::Scoring => "score_thought",
+            RequestKind::FillInMiddle => "generate_fim",
+        }
+    }
+
+    pub fn default_options(self) -> GenerateOptions {
+        match self {
+            // Two numbers; anything after a blank line is commentary we'd discard
+            RequestKind::DimensionalEvaluation => GenerateOptions {
+                temperature: Some(0.0),
+                num_predict: Some(32),
+                stop: vec!["\n\n".to_string()],
+                ..Default::default()
+            },
+            RequestKind::Thought => GenerateOptions {
+                temperature: Some(0.8),
+                num_predict: Some(512),
+                ..Default::default()
+            },
+            // Structured, but should still adapt to the thoughts it is given
+            RequestKind::Plan => GenerateOptions {
+                temperature: Some(0.4),
+                num_predict: Some(1024),
+                ..Default::default()
+            },
+            RequestKind::RealTimeContext => GenerateOptions {
+                temperature: Some(0.3),
+                num_predict: Some(768),
+                ..Default::default()
+            },
+            // Summaries should stay close to the source material
+            RequestKind::Compression => GenerateOptions {
+                temperature: Some(0.2),
+                num_predict: Some(1024),
+                ..Default::default()
+            },
+            RequestKind::Scoring => GenerateOptions {
+                temperature: Some(0.0),
+                num_predict: Some(16),
+                stop: vec!["\n".to_string()],
+                ..Default::default()
+            },
+            // Infills are usually short and should match the surrounding code
+            RequestKind::FillInMiddle => GenerateOptions {
+                temperature: Some(0.2),
+                num_predict: Some(256),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// How long Ollama keeps the model loaded after a request: a duration string
+// such as "30m", or a number of seconds where a negative value means forever.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Duration(String),
+    Seconds(i64),
+}
+
+impl KeepAlive {
+    pub fn forever() -> Self {
+        KeepAlive::Seconds(-1)
+    }
 }
 
 #[derive(Serialize)]
-struct GenerateRequest {
-    model: String,
-    prompt: String,
+struct GenerateRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<&'a GenerateOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a KeepAlive>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    raw: bool,
+    // Base64-encoded, for vision models
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    images: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<&'a [i64]>,
+    // Text after the gap, for fill-in-the-middle models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suffix: Option<&'a str>,
+}
+
+// Request fields only a few callers set, kept together so the send path
+// doesn't grow a parameter for each
+#[derive(Clone, Copy, Default)]
+struct GenerateExtras<'a> {
+    images: &'a [String],
+    context: Option<&'a [i64]>,
+    suffix: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct GenerateResponse {
+    response: String,
+    // Encodes the conversation so far, for continuing it in a later request
+    #[serde(default)]
+    context: Option<Vec<i64>>,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<&'a GenerateOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a KeepAlive>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ChatMessage,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub digest: Option<String>,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct PullRequest<'a> {
+    name: &'a str,
     stream: bool,
 }
 
-#[derive(Deserialize)]
-struct GenerateResponse {
-    response: String,
-}
+#[derive(Deserialize)]
+struct PullChunk {
+    #[serde(flatten)]
+    progress: PullProgress,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TagsResponse {
+    models: Vec<ModelTag>,
+}
+
+#[derive(Deserialize)]
+struct ModelTag {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct ShowRequest<'a> {
+    model: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ShowResponse {
+    // Only reported by newer Ollama versions
+    #[serde(default)]
+    capabilities: Vec<String>,
+    #[serde(default)]
+    details: ModelDetails,
+}
+
+#[derive(Default, Deserialize)]
+struct ModelDetails {
+    #[serde(default)]
+    families: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct PlanJson {
+    summary: String,
+    nodes: Vec<PlanNodeJson>,
+    #[serde(default = "default_plan_score")]
+    score: f64,
+}
+
+#[derive(Deserialize)]
+struct PlanNodeJson {
+    title: String,
+    description: String,
+    #[serde(default)]
+    estimated_completion: f64,
+    #[serde(default)]
+    depends_on: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct RealTimeContextJson {
+    #[serde(default)]
+    market_trends: Vec<String>,
+    #[serde(default)]
+    technological_developments: Vec<String>,
+    #[serde(default)]
+    current_events: Vec<String>,
+    #[serde(default)]
+    user_interactions: Vec<String>,
+    #[serde(default)]
+    environmental_data: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    mission_progress: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GenerateChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+}
+
+// Collects client settings and applies them in one validated step. Anything
+// left unset keeps the same default as OllamaClient::new.
+#[derive(Clone, Debug)]
+pub struct OllamaClientBuilder {
+    model: String,
+    base_url: Option<String>,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    embedding_model: Option<String>,
+    use_chat: bool,
+    persona: Option<String>,
+    prohibited_terms: Option<Vec<String>>,
+    prohibited_patterns: Vec<String>,
+    context_window: Option<usize>,
+    cache_capacity: Option<usize>,
+    keep_alive: Option<KeepAlive>,
+    prompts: Option<PromptTemplates>,
+    dry_run: bool,
+    max_in_flight: Option<usize>,
+    fallback_models: Vec<String>,
+    min_thought_energy: Option<f64>,
+    user_agent: Option<String>,
+    parser_config: Option<ParserConfig>,
+    response_parser: Option<BuilderParser>,
+    method_options: HashMap<RequestKind, GenerateOptions>,
+}
+
+// Lets the builder keep deriving Debug while holding a parser trait object
+#[derive(Clone)]
+struct BuilderParser(Arc<dyn ResponseParser>);
+
+impl std::fmt::Debug for BuilderParser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ResponseParser({:?})", self.0.format())
+    }
+}
+
+impl Default for OllamaClientBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_MODEL)
+    }
+}
+
+impl OllamaClientBuilder {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            base_url: None,
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            request_timeout: Duration::from_secs(API_TIMEOUT_SECS),
+            embedding_model: None,
+            use_chat: false,
+            persona: None,
+            prohibited_terms: None,
+            prohibited_patterns: Vec::new(),
+            context_window: None,
+            cache_capacity: None,
+            keep_alive: None,
+            prompts: None,
+            dry_run: false,
+            max_in_flight: None,
+            fallback_models: Vec::new(),
+            min_thought_energy: None,
+            user_agent: None,
+            parser_config: None,
+            response_parser: None,
+            method_options: HashMap::new(),
+        }
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    pub fn chat_mode(mut self, enabled: bool) -> Self {
+        self.use_chat = enabled;
+        self
+    }
+
+    pub fn persona(mut self, persona: impl Into<String>) -> Self {
+        self.persona = Some(persona.into());
+        self
+    }
+
+    pub fn prohibited_terms<I, S>(mut self, terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.prohibited_terms = Some(terms.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn prohibited_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.prohibited_patterns.push(pattern.into());
+        self
+    }
+
+    pub fn context_window(mut self, tokens: usize) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    pub fn cache(mut self, capacity: usize) -> Self {
+        self.cache_capacity = Some(capacity);
+        self
+    }
+
+    pub fn keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    pub fn prompt_templates(mut self, prompts: PromptTemplates) -> Self {
+        self.prompts = Some(prompts);
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn max_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = Some(limit);
+        self
+    }
+
+    pub fn fallback_model(mut self, model: impl Into<String>) -> Self {
+        self.fallback_models.push(model.into());
+        self
+    }
+
+    pub fn min_thought_energy(mut self, energy: f64) -> Self {
+        self.min_thought_energy = Some(energy);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn parser_config(mut self, config: ParserConfig) -> Self {
+        self.parser_config = Some(config);
+        self
+    }
+
+    // Applied after parser_config, so it wins over the SectionParser that
+    // setting a config installs
+    pub fn response_parser(mut self, parser: impl ResponseParser + 'static) -> Self {
+        self.response_parser = Some(BuilderParser(Arc::new(parser)));
+        self
+    }
+
+    pub fn method_options(mut self, kind: RequestKind, options: GenerateOptions) -> Self {
+        self.method_options.insert(kind, options);
+        self
+    }
+
+    pub fn build(self) -> Result<OllamaClient, OllamaError> {
+        if self.connect_timeout.is_zero() || self.request_timeout.is_zero() {
+            return Err(OllamaError::InvalidConfig("timeouts must be non-zero".to_string()));
+        }
+        if self.context_window == Some(0) {
+            return Err(OllamaError::InvalidConfig("context window must be at least one token".to_string()));
+        }
+        if self.cache_capacity == Some(0) {
+            return Err(OllamaError::InvalidConfig("cache capacity must be at least one entry".to_string()));
+        }
+
+        let mut client = OllamaClient::new(self.model)?
+            .with_timeouts(self.connect_timeout, self.request_timeout)?
+            .with_chat_mode(self.use_chat)
+            .with_dry_run(self.dry_run);
+
+        if let Some(base_url) = &self.base_url {
+            client = client.with_base_url(base_url)?;
+        }
+        if let Some(model) = self.embedding_model {
+            client = client.with_embedding_model(model);
+        }
+        if let Some(persona) = self.persona {
+            client = client.with_persona(persona);
+        }
+        if let Some(terms) = self.prohibited_terms {
+            client = client.with_prohibited_terms(terms);
+        }
+        for pattern in &self.prohibited_patterns {
+            client = client.with_prohibited_pattern(pattern)?;
+        }
+        if let Some(tokens) = self.context_window {
+            client = client.with_context_window(tokens);
+        }
+        if let Some(capacity) = self.cache_capacity {
+            client = client.with_cache(capacity);
+        }
+        if let Some(keep_alive) = self.keep_alive {
+            client = client.with_keep_alive(keep_alive);
+        }
+        if let Some(prompts) = self.prompts {
+            client = client.with_prompt_templates(prompts);
+        }
+        if let Some(limit) = self.max_in_flight {
+            client = client.with_max_in_flight(limit)?;
+        }
+        for model in &self.fallback_models {
+            client = client.with_fallback_model(model)?;
+        }
+        if let Some(energy) = self.min_thought_energy {
+            client = client.with_min_thought_energy(energy);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            client = client.with_user_agent(user_agent)?;
+        }
+        if let Some(config) = self.parser_config {
+            client = client.with_parser_config(config);
+        }
+        if let Some(BuilderParser(parser)) = self.response_parser {
+            client.parser = parser;
+        }
+        for (kind, options) in self.method_options {
+            client = client.with_method_options(kind, options);
+        }
+
+        Ok(client)
+    }
+}
+
+impl OllamaClient {
+    pub fn builder(model: impl Into<String>) -> OllamaClientBuilder {
+        OllamaClientBuilder::new(model)
+    }
+
+    pub fn new(model: String) -> Result<Self, OllamaError> {
+        let model = validate_model_name(&model)?;
+        let client = build_http_client(
+            Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            Duration::from_secs(API_TIMEOUT_SECS),
+        )?;
+
+        Ok(Self {
+            client,
+            base_url: DEFAULT_OLLAMA_API_URL.to_string(),
+            model,
+            embedding_model: None,
+            use_chat: false,
+            persona: DEFAULT_THOUGHT_PERSONA.to_string(),
+            prohibited_terms: DEFAULT_PROHIBITED_TERMS.iter().map(|t| t.to_string()).collect(),
+            prohibited_patterns: Vec::new(),
+            context_window: None,
+            cache: None,
+            embedding_cache: Arc::new(Mutex::new(HashMap::new())),
+            keep_alive: None,
+            method_options: HashMap::new(),
+            prompts: PromptTemplates::default(),
+            dry_run: false,
+            generation_slots: None,
+            fallback_models: Vec::new(),
+            parser: Arc::new(SectionParser::default()),
+            parser_config: ParserConfig::default(),
+            min_thought_energy: DEFAULT_MIN_THOUGHT_ENERGY,
+            user_agent: None,
+        })
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Result<Self, OllamaError> {
+        self.base_url = validate_base_url(base_url)?;
+        Ok(self)
+    }
+
+    // The connect timeout bounds establishing the TCP connection, the request
+    // timeout bounds the whole call including generation time.
+    pub fn with_timeouts(mut self, connect: Duration, request: Duration) -> Result<Self, OllamaError> {
+        self.client = build_http_client(connect, request)?;
+        Ok(self)
+    }
+
+    // Sets num_ctx on every request and rejects prompts estimated to be
+    // larger than it, since Ollama would otherwise truncate them silently.
+    pub fn with_context_window(mut self, tokens: usize) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    // Caches generate responses for calls made with temperature 0, keyed on
+    // the full request. Sampled calls always go to the server.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Arc::new(Mutex::new(ResponseCache::new(capacity))));
+        self
+    }
+
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().clear();
+        }
+        self.embedding_cache.lock().unwrap().clear();
+    }
+
+    pub fn with_keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    pub fn with_prompt_templates(mut self, prompts: PromptTemplates) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    // Makes every generation fail with OllamaError::DryRun carrying the fully
+    // rendered prompt, without touching the network
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    // Caps how many generations this client and all its clones run against
+    // the server at once; further calls wait for a free slot. A limit of
+    // zero would block every call, so it is rejected.
+    pub fn with_max_in_flight(mut self, limit: usize) -> Result<Self, OllamaError> {
+        if limit == 0 {
+            return Err(OllamaError::InvalidConfig("at least one generation must be allowed in flight".to_string()));
+        }
+        self.generation_slots = Some(Arc::new(Semaphore::new(limit)));
+        Ok(self)
+    }
+
+    // Adds a model to try, after the primary and any earlier fallbacks, when
+    // generation fails because a model is missing or can't be loaded. Covers
+    // generate, chat, streaming and image requests; context-carrying calls
+    // stay on the primary, and explicit model overrides are sent as given.
+    pub fn with_fallback_model(mut self, model: &str) -> Result<Self, OllamaError> {
+        self.fallback_models.push(validate_model_name(model)?);
+        Ok(self)
+    }
+
+    // Headers used to read thoughts, streamed plans and the reformat retry.
+    // Also swaps the response parser for a SectionParser using them, so set
+    // a custom parser after this, not before.
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.parser = Arc::new(SectionParser::with_config(config.clone()));
+        self.parser_config = config;
+        self
+    }
+
+    // Cells whose energy_level is at or below this get InsufficientEnergy
+    // from thought generation instead of a model call
+    pub fn with_min_thought_energy(mut self, energy: f64) -> Self {
+        self.min_thought_energy = energy;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Result<Self, OllamaError> {
+        let value = HeaderValue::from_str(user_agent.trim())
+            .map_err(|e| OllamaError::InvalidConfig(format!("invalid user agent '{}': {}", user_agent, e)))?;
+        self.user_agent = Some(value);
+        Ok(self)
+    }
+
+    // Starts a request to an API endpoint, tagged with a fresh request ID so
+    // it can be found in the server's logs
+    fn request(&self, method: Method, endpoint: &str) -> RequestBuilder {
+        let request_id = Uuid::new_v4();
+        tracing::debug!(%request_id, endpoint, "sending Ollama request");
+        let request = self.client
+            .request(method, format!("{}/{}", self.base_url, endpoint))
+            .header(REQUEST_ID_HEADER, request_id.to_string());
+        match &self.user_agent {
+            Some(user_agent) => request.header(reqwest::header::USER_AGENT, user_agent.clone()),
+            None => request,
+        }
+    }
+
+    // Waits for a generation slot when a limit is configured. The slot is
+    // released when the returned permit is dropped.
+    async fn acquire_generation_slot(&self) -> Option<OwnedSemaphorePermit> {
+        let slots = self.generation_slots.as_ref()?;
+        Some(slots.clone().acquire_owned().await.expect("generation semaphore is never closed"))
+    }
+
+    // Swaps how plan and context responses are read, and with it the format
+    // those prompts ask for. Defaults to SectionParser.
+    pub fn with_response_parser(mut self, parser: impl ResponseParser + 'static) -> Self {
+        self.parser = Arc::new(parser);
+        self
+    }
+
+    // Replaces the built-in preset for one kind of request entirely
+    pub fn with_method_options(mut self, kind: RequestKind, options: GenerateOptions) -> Self {
+        self.method_options.insert(kind, options);
+        self
+    }
+
+    pub fn check_prompt_budget(&self, prompt: &str) -> Result<(), OllamaError> {
+        self.check_prompt_budget_within(prompt, self.context_window)
+    }
+
+    fn check_prompt_budget_within(&self, prompt: &str, limit: Option<usize>) -> Result<(), OllamaError> {
+        let Some(limit) = limit else {
+            return Ok(());
+        };
+        let estimated_tokens = estimate_tokens(prompt);
+        if estimated_tokens > limit {
+            return Err(OllamaError::PromptTooLarge { estimated_tokens, limit });
+        }
+        Ok(())
+    }
+
+    // Embeddings fall back to the generation model when no dedicated
+    // embedding model (e.g. nomic-embed-text) is configured.
+    pub fn with_embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    // Sends the persona as a system message via /api/chat instead of inlining
+    // it into a single /api/generate prompt.
+    pub fn with_chat_mode(mut self, enabled: bool) -> Self {
+        self.use_chat = enabled;
+        self
+    }
+
+    // System instruction placed ahead of every thought prompt
+    pub fn with_persona(mut self, persona: impl Into<String>) -> Self {
+        self.persona = persona.into();
+        self
+    }
+
+    // Replaces the default list; terms match case-insensitively as substrings
+    pub fn with_prohibited_terms<I, S>(mut self, terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.prohibited_terms = terms.into_iter().map(|t| t.into().to_lowercase()).collect();
+        self
+    }
+
+    // Adds a case-insensitive regex alongside the plain terms, e.g. `emergence\s*[:=]`
+    pub fn with_prohibited_pattern(mut self, pattern: &str) -> Result<Self, OllamaError> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(OllamaError::InvalidPattern)?;
+        self.prohibited_patterns.push(regex);
+        Ok(self)
+    }
+
+    async fn generate(&self, prompt: &str) -> Result<String, OllamaError> {
+        self.send_generate(prompt, None, None).await
+    }
+
+    async fn generate_for(&self, kind: RequestKind, prompt: &str) -> Result<String, OllamaError> {
+        let started = Instant::now();
+        let response = self.send_generate(prompt, Some(&self.options_for(kind)), None).await;
+        telemetry::record_method_latency(kind.label(), started.elapsed());
+        response
+    }
+
+    // Constrains the model to emit a single valid JSON value
+    async fn generate_json(&self, kind: RequestKind, prompt: &str) -> Result<String, OllamaError> {
+        let started = Instant::now();
+        let response = self.send_generate(prompt, Some(&self.options_for(kind)), Some("json")).await;
+        telemetry::record_method_latency(kind.label(), started.elapsed());
+        response
+    }
+
+    fn options_for(&self, kind: RequestKind) -> GenerateOptions {
+        self.method_options
+            .get(&kind)
+            .cloned()
+            .unwrap_or_else(|| kind.default_options())
+    }
+
+    pub async fn generate_cancellable(
+        &self,
+        prompt: &str,
+        token: &CancellationToken,
+    ) -> Result<String, OllamaError> {
+        with_cancellation(token, self.generate(prompt)).await
+    }
+
+    pub async fn generate_with_options(
+        &self,
+        prompt: &str,
+        options: &GenerateOptions,
+    ) -> Result<String, OllamaError> {
+        self.send_generate(prompt, Some(options), None).await
+    }
+
+    // Routes a single request to `model` instead of the configured one
+    pub async fn generate_with_model(&self, model: &str, prompt: &str) -> Result<String, OllamaError> {
+        let model = validate_model_name(model)?;
+        self.send_generate_to(&model, prompt, None, None, &[]).await
+    }
+
+    pub async fn generate_with_model_options(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: &GenerateOptions,
+    ) -> Result<String, OllamaError> {
+        let model = validate_model_name(model)?;
+        self.send_generate_to(&model, prompt, Some(options), None, &[]).await
+    }
+
+    // Like generate, but also reports which model in the fallback chain
+    // served the request
+    pub async fn generate_with_fallback(&self, prompt: &str) -> Result<Generation, OllamaError> {
+        self.send_generate_with_fallback(prompt, None, None).await
+    }
+
+    async fn send_generate(
+        &self,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+        format: Option<&str>,
+    ) -> Result<String, OllamaError> {
+        self.send_generate_with_fallback(prompt, options, format)
+            .await
+            .map(|generation| generation.text)
+    }
+
+    async fn send_generate_with_fallback(
+        &self,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+        format: Option<&str>,
+    ) -> Result<Generation, OllamaError> {
+        self.send_with_fallback(prompt, options, format, GenerateExtras::default())
+            .await
+            .map(|(response, model)| Generation { text: response.response, model })
+    }
+
+    async fn send_with_fallback(
+        &self,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+        format: Option<&str>,
+        extras: GenerateExtras<'_>,
+    ) -> Result<(GenerateResponse, String), OllamaError> {
+        self.with_fallback(|model| self.send_generate_request(model, prompt, options, format, extras)).await
+    }
+
+    // Runs `send` for the primary model, then each fallback in order, moving
+    // on only when a model is unavailable. Any other error is returned
+    // immediately. Returns the result along with the model that served it.
+    async fn with_fallback<'a, T, F, Fut>(&'a self, mut send: F) -> Result<(T, String), OllamaError>
+    where
+        F: FnMut(&'a str) -> Fut,
+        Fut: Future<Output = Result<T, OllamaError>>,
+    {
+        let mut result = send(&self.model).await.map(|value| (value, self.model.clone()));
+        for model in &self.fallback_models {
+            match &result {
+                Err(e) if e.is_model_unavailable() => {
+                    tracing::warn!(fallback = %model, error = %e, "model unavailable, trying the next fallback");
+                }
+                _ => break,
+            }
+            result = send(model).await.map(|value| (value, model.clone()));
+        }
+        result
+    }
+
+    // Single-shot generation that also carries Ollama's context array: pass
+    // the array from the previous turn to continue from it without resending
+    // the history, and keep the returned one for the next turn. The array is
+    // only meaningful to the model that produced it, so these requests always
+    // go to the configured model and never fall back.
+    pub async fn generate_with_context(
+        &self,
+        prompt: &str,
+        context: Option<&[i64]>,
+    ) -> Result<(String, Vec<i64>), OllamaError> {
+        let extras = GenerateExtras { context, ..Default::default() };
+        let response = self.send_generate_request(&self.model, prompt, None, None, extras).await?;
+        Ok((response.response, response.context.unwrap_or_default()))
+    }
+
+    // Fill-in-the-middle: the model writes what belongs between `prefix` and
+    // `suffix`. Needs a model whose template supports it, such as
+    // codellama:code or qwen2.5-coder; Ollama rejects the request for any
+    // other model, which surfaces as InsertUnsupported.
+    pub async fn generate_fim(&self, prefix: &str, suffix: &str) -> Result<String, OllamaError> {
+        let kind = RequestKind::FillInMiddle;
+        let extras = GenerateExtras { suffix: Some(suffix), ..Default::default() };
+        let started = Instant::now();
+        let response = self.send_with_fallback(prefix, Some(&self.options_for(kind)), None, extras).await;
+        telemetry::record_method_latency(kind.label(), started.elapsed());
+        response.map(|(response, _)| response.response)
+    }
+
+    async fn send_generate_to(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+        format: Option<&str>,
+        images: &[String],
+    ) -> Result<String, OllamaError> {
+        let extras = GenerateExtras { images, ..Default::default() };
+        self.send_generate_request(model, prompt, options, format, extras)
+            .await
+            .map(|response| response.response)
+    }
+
+    // Checks the prompt against the effective window and fills in num_ctx
+    // from the configured window when the caller didn't set one
+    fn apply_context_window(
+        &self,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+    ) -> Result<Option<GenerateOptions>, OllamaError> {
+        let explicit_ctx = options.and_then(|o| o.num_ctx);
+        self.check_prompt_budget_within(prompt, explicit_ctx.or(self.context_window))?;
+        Ok(match (self.context_window, explicit_ctx) {
+            (Some(num_ctx), None) => Some(GenerateOptions {
+                num_ctx: Some(num_ctx),
+                ..options.cloned().unwrap_or_default()
+            }),
+            _ => options.cloned(),
+        })
+    }
+
+    #[tracing::instrument(name = "ollama_generate", skip_all, fields(model = %model, prompt_len = prompt.len()))]
+    async fn send_generate_request(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+        format: Option<&str>,
+        extras: GenerateExtras<'_>,
+    ) -> Result<GenerateResponse, OllamaError> {
+        if self.dry_run {
+            return Err(OllamaError::DryRun { prompt: prompt.to_string() });
+        }
+        let options = self.apply_context_window(prompt, options)?;
+        let options = options.as_ref();
+
+        let request = GenerateRequest {
+            model,
+            prompt,
+            stream: false,
+            options,
+            format,
+            keep_alive: self.keep_alive.as_ref(),
+            raw: options.is_some_and(|o| o.raw),
+            images: extras.images,
+            context: extras.context,
+            suffix: extras.suffix,
+        };
+
+        // The cache only holds text, so callers tracking context always go
+        // to the server
+        let deterministic = options.is_some_and(|o| o.temperature == Some(0.0));
+        let cache_key = match &self.cache {
+            Some(cache) if deterministic && extras.context.is_none() => {
+                let key = serde_json::to_string(&request)?;
+                if let Some(hit) = cache.lock().unwrap().get(&key) {
+                    tracing::debug!("served from response cache");
+                    return Ok(GenerateResponse { response: hit, context: None });
+                }
+                Some((cache, key))
+            }
+            _ => None,
+        };
+
+        let _slot = self.acquire_generation_slot().await;
+        let started = Instant::now();
+        let response = self.request(Method::POST, "generate")
+            .json(&request)
+            .send()
+            .await?;
+
+        let response = match check_status(response).await {
+            // Ollama refuses a suffix for models whose template can't infill
+            Err(OllamaError::Server { status: 400, message })
+                if extras.suffix.is_some() && message.contains("does not support insert") =>
+            {
+                return Err(OllamaError::InsertUnsupported(model.to_string()));
+            }
+            response => response?,
+        }
+        .json::<GenerateResponse>()
+        .await?;
+        telemetry::record_request("generate", started.elapsed());
+
+        tracing::debug!(response = %response.response, "generate response");
+        let text = normalize_text(&response.response);
+        if text.trim().is_empty() {
+            return Err(OllamaError::EmptyResponse);
+        }
+
+        if let Some((cache, key)) = cache_key {
+            cache.lock().unwrap().insert(key, text.clone());
+        }
+
+        Ok(GenerateResponse { response: text, context: response.context })
+    }
+
+    // An empty prompt makes Ollama load the model without generating anything,
+    // so the first real request doesn't pay for the load.
+    pub async fn warm_up(&self) -> Result<(), OllamaError> {
+        let request = GenerateRequest {
+            model: &self.model,
+            prompt: "",
+            stream: false,
+            options: None,
+            format: None,
+            keep_alive: self.keep_alive.as_ref(),
+            raw: false,
+            images: &[],
+            context: None,
+            suffix: None,
+        };
+
+        let response = self.request(Method::POST, "generate")
+            .json(&request)
+            .send()
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+
+    // Cheap reachability check for readiness probes; /tags never touches a model
+    pub async fn ping(&self) -> Result<(), OllamaError> {
+        let response = self.request(Method::GET, "tags")
+            .timeout(Duration::from_secs(PING_TIMEOUT_SECS))
+            .send()
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+
+    // Asks the server whether the model can take images. Older servers don't
+    // list capabilities, so a CLIP projector among the families counts too.
+    pub async fn supports_vision(&self, model: &str) -> Result<bool, OllamaError> {
+        let response = self.request(Method::POST, "show")
+            .json(&ShowRequest { model })
+            .send()
+            .await?;
+
+        let show = check_status(response)
+            .await?
+            .json::<ShowResponse>()
+            .await?;
+
+        let families = show.details.families.unwrap_or_default();
+        Ok(show.capabilities.iter().any(|c| c == "vision")
+            || families.iter().any(|f| f == "clip" || f == "mllama"))
+    }
+
+    // Sends images (raw bytes, e.g. PNG) alongside the prompt. Fails with
+    // VisionUnsupported before generating if the model that would serve the
+    // request can't see them.
+    pub async fn generate_with_images(&self, prompt: &str, images: &[Vec<u8>]) -> Result<String, OllamaError> {
+        let images: Vec<String> = images.iter().map(|image| BASE64.encode(image)).collect();
+        let images = images.as_slice();
+        self.with_fallback(|model| async move {
+            if !self.dry_run && !self.supports_vision(model).await? {
+                return Err(OllamaError::VisionUnsupported(model.to_string()));
+            }
+            self.send_generate_to(model, prompt, None, None, images).await
+        })
+        .await
+        .map(|(text, _)| text)
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<String>, OllamaError> {
+        let response = self.request(Method::GET, "tags")
+            .send()
+            .await?;
+
+        let tags = check_status(response)
+            .await?
+            .json::<TagsResponse>()
+            .await?;
+
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    pub async fn ensure_model_available(&self) -> Result<(), OllamaError> {
+        let models = self.list_models().await?;
+        if models.iter().any(|m| model_matches(m, &self.model)) {
+            Ok(())
+        } else {
+            Err(OllamaError::ModelNotFound(self.model.clone()))
+        }
+    }
+
+    // Consuming variant of ensure_model_available for use right after construction
+    pub async fn checked(self) -> Result<Self, OllamaError> {
+        self.ensure_model_available().await?;
+        Ok(self)
+    }
+
+    // Downloads `name` onto the server, reporting each progress update from
+    // /api/pull. Pulling a model that is already present just verifies it.
+    pub async fn pull_model<F>(&self, name: &str, mut on_progress: F) -> Result<(), OllamaError>
+    where
+        F: FnMut(&PullProgress),
+    {
+        let response = self.request(Method::POST, "pull")
+            .timeout(Duration::from_secs(PULL_TIMEOUT_SECS))
+            .json(&PullRequest { name, stream: true })
+            .send()
+            .await?;
+        let response = check_status(response).await?;
+
+        let mut chunks = Box::pin(ndjson_stream::<PullChunk, _, _>(Box::pin(response.bytes_stream())));
+        let mut succeeded = false;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if let Some(message) = chunk.error {
+                return Err(OllamaError::PullFailed { model: name.to_string(), message });
+            }
+            succeeded = chunk.progress.status == "success";
+            on_progress(&chunk.progress);
+        }
+
+        if succeeded {
+            Ok(())
+        } else {
+            Err(OllamaError::PullFailed {
+                model: name.to_string(),
+                message: "stream ended before the pull completed".to_string(),
+            })
+        }
+    }
+
+    pub async fn chat(&self, messages: &[ChatMessage]) -> Result<String, OllamaError> {
+        self.send_chat(messages, None).await
+    }
+
+    async fn send_chat(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<&GenerateOptions>,
+    ) -> Result<String, OllamaError> {
+        self.with_fallback(|model| self.send_chat_to(model, messages, options))
+            .await
+            .map(|(text, _)| text)
+    }
+
+    #[tracing::instrument(name = "ollama_chat", skip_all, fields(model = %model, messages = messages.len()))]
+    async fn send_chat_to(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        options: Option<&GenerateOptions>,
+    ) -> Result<String, OllamaError> {
+        if self.dry_run {
+            let prompt = messages.iter()
+                .map(|m| format!("{:?}: {}", m.role, m.content))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            return Err(OllamaError::DryRun { prompt });
+        }
+        let transcript: String = messages.iter().map(|m| m.content.as_str()).collect();
+        let options = self.apply_context_window(&transcript, options)?;
+
+        let request = ChatRequest {
+            model,
+            messages,
+            stream: false,
+            options: options.as_ref(),
+            keep_alive: self.keep_alive.as_ref(),
+        };
+
+        let _slot = self.acquire_generation_slot().await;
+        let started = Instant::now();
+        let response = self.request(Method::POST, "chat")
+            .json(&request)
+            .send()
+            .await?;
+
+        let response = check_status(response)
+            .await?
+            .json::<ChatResponse>()
+            .await?;
+        telemetry::record_request("chat", started.elapsed());
+
+        tracing::debug!(response = %response.message.content, "chat response");
+        let text = normalize_text(&response.message.content);
+        if text.trim().is_empty() {
+            return Err(OllamaError::EmptyResponse);
+        }
+        Ok(text)
+    }
+
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>, OllamaError> {
+        let model = self.embedding_model.as_deref().unwrap_or(&self.model);
+        let key = (model.to_string(), text.to_string());
+        if let Some(cached) = self.embedding_cache.lock().unwrap().get(&key) {
+            return Ok(cached.clone());
+        }
+
+        let request = EmbeddingRequest {
+            model,
+            prompt: text,
+        };
+
+        let response = self.request(Method::POST, "embeddings")
+            .json(&request)
+            .send()
+            .await?;
+
+        let response = check_status(response)
+            .await?
+            .json::<EmbeddingResponse>()
+            .await?;
+
+        if response.embedding.is_empty() {
+            return Err(OllamaError::EmptyResponse);
+        }
+
+        self.embedding_cache
+            .lock()
+            .unwrap()
+            .insert(key, response.embedding.clone());
+        Ok(response.embedding)
+    }
+
+    // Returns up to `k` thoughts ordered by cosine similarity to the query.
+    // Thoughts that fail to embed are skipped rather than failing the ranking.
+    pub async fn rank_thoughts_by_similarity(
+        &self,
+        query: &str,
+        thoughts: &[Thought],
+        k: usize,
+    ) -> Result<Vec<(Thought, f64)>, OllamaError> {
+        let query_embedding = self.embed(query).await?;
+        let contents: Vec<String> = thoughts.iter().map(|t| t.content.clone()).collect();
+        let embeddings = self.embed_many_with(&contents, &BatchOptions::default()).await;
+
+        let mut ranked = Vec::with_capacity(thoughts.len());
+        for (thought, embedding) in thoughts.iter().zip(embeddings) {
+            match embedding {
+                Ok(embedding) => {
+                    ranked.push((thought.clone(), cosine_similarity(&query_embedding, &embedding)));
+                }
+                Err(e) => log_warning(&format!("Skipping thought {} in similarity ranking: {}", thought.id, e)),
+            }
+        }
+
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(k);
+        Ok(ranked)
+    }
+
+    // Drops thoughts whose embedding is at least `threshold` similar to a
+    // more relevant thought that was kept. Survivors keep their input order;
+    // thoughts that fail to embed are kept since they can't be compared.
+    pub async fn dedupe_thoughts(
+        &self,
+        thoughts: &[Thought],
+        threshold: f64,
+    ) -> Result<Vec<Thought>, OllamaError> {
+        let contents: Vec<String> = thoughts.iter().map(|t| t.content.clone()).collect();
+        let embeddings = self.embed_many_with(&contents, &BatchOptions::default()).await;
+
+        let mut by_relevance: Vec<usize> = (0..thoughts.len()).collect();
+        by_relevance.sort_by(|&a, &b| thoughts[b].relevance_score.value().total_cmp(&thoughts[a].relevance_score.value()));
+
+        let mut keep = vec![false; thoughts.len()];
+        let mut kept_embeddings: Vec<&Vec<f32>> = Vec::new();
+        for index in by_relevance {
+            match &embeddings[index] {
+                Ok(embedding) => {
+                    let duplicate = kept_embeddings
+                        .iter()
+                        .any(|kept| cosine_similarity(kept, embedding) >= threshold);
+                    if !duplicate {
+                        keep[index] = true;
+                        kept_embeddings.push(embedding);
+                    }
+                }
+                Err(e) => {
+                    log_warning(&format!("Keeping thought {} without deduplication: {}", thoughts[index].id, e));
+                    keep[index] = true;
+                }
+            }
+        }
+
+        Ok(thoughts.iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(thought, _)| thought.clone())
+            .collect())
+    }
+
+    // Groups thoughts into at most `k` clusters of similar embeddings, for
+    // summarizing each theme on its own. Clusters are ordered by their first
+    // thought and keep input order inside; thoughts that fail to embed are
+    // left out.
+    pub async fn cluster_thoughts(&self, thoughts: &[Thought], k: usize) -> Result<Vec<Vec<Thought>>, OllamaError> {
+        let contents: Vec<String> = thoughts.iter().map(|t| t.content.clone()).collect();
+        let embeddings = self.embed_many_with(&contents, &BatchOptions::default()).await;
+
+        let mut embedded = Vec::with_capacity(thoughts.len());
+        let mut vectors = Vec::with_capacity(thoughts.len());
+        for (thought, embedding) in thoughts.iter().zip(embeddings) {
+            match embedding {
+                Ok(embedding) => {
+                    embedded.push(thought);
+                    vectors.push(embedding);
+                }
+                Err(e) => log_warning(&format!("Leaving thought {} out of clustering: {}", thought.id, e)),
+            }
+        }
+        if embedded.is_empty() && !thoughts.is_empty() {
+            return Err(OllamaError::InvalidFormat {
+                expected: "at least one thought that could be embedded".to_string(),
+                got: format!("{} thoughts", thoughts.len()),
+            });
+        }
+
+        let assignments = kmeans(&vectors, k);
+        let mut clusters: Vec<(usize, Vec<Thought>)> = Vec::new();
+        for (thought, cluster) in embedded.into_iter().zip(assignments) {
+            match clusters.iter_mut().find(|(id, _)| *id == cluster) {
+                Some((_, members)) => members.push(thought.clone()),
+                None => clusters.push((cluster, vec![thought.clone()])),
+            }
+        }
+        Ok(clusters.into_iter().map(|(_, members)| members).collect())
+    }
+
+    pub async fn embed_many(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, OllamaError> {
+        self.embed_many_with(texts, &BatchOptions::default())
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    // Embeds up to `max_concurrency` texts at a time. The output lines up with
+    // `texts` index for index, so one failed input doesn't lose the others.
+    pub async fn embed_many_with(
+        &self,
+        texts: &[String],
+        options: &BatchOptions,
+    ) -> Vec<Result<Vec<f32>, OllamaError>> {
+        let requests: Vec<_> = texts.iter().map(|text| self.embed(text)).collect();
+
+        futures::stream::iter(requests)
+            .buffered(options.max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    // Yields response tokens as Ollama emits them; the stream ends after the
+    // chunk marked `done`.
+    pub async fn generate_stream(
+        &self,
+        prompt: &str,
+    ) -> Result<impl Stream<Item = Result<String, OllamaError>>, OllamaError> {
+        self.send_generate_stream(prompt, None).await
+    }
+
+    // Falls back like generate, but only while opening the stream; once
+    // tokens flow, errors come through the stream itself
+    async fn send_generate_stream(
+        &self,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+    ) -> Result<impl Stream<Item = Result<String, OllamaError>>, OllamaError> {
+        self.with_fallback(|model| self.send_generate_stream_to(model, prompt, options))
+            .await
+            .map(|(tokens, _)| tokens)
+    }
+
+    async fn send_generate_stream_to(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: Option<&GenerateOptions>,
+    ) -> Result<impl Stream<Item = Result<String, OllamaError>>, OllamaError> {
+        if self.dry_run {
+            return Err(OllamaError::DryRun { prompt: prompt.to_string() });
+        }
+        let options = self.apply_context_window(prompt, options)?;
+        let request = GenerateRequest {
+            model,
+            prompt,
+            stream: true,
+            options: options.as_ref(),
+            format: None,
+            keep_alive: self.keep_alive.as_ref(),
+            raw: false,
+            images: &[],
+            context: None,
+            suffix: None,
+        };
+
+        // The slot stays taken until the stream is finished or dropped
+        let slot = self.acquire_generation_slot().await;
+        let response = self.request(Method::POST, "generate")
+            .json(&request)
+            .send()
+            .await?;
+        let response = check_status(response).await?;
+
+        let tokens = ndjson_stream::<GenerateChunk, _, _>(Box::pin(response.bytes_stream()))
+            .scan((false, slot), |(done, _slot), chunk| {
+                if *done {
+                    return future::ready(None);
+                }
+                future::ready(Some(chunk.map(|c| {
+                    *done = c.done;
+                    c.response
+                })))
+            })
+            .try_filter(|token| future::ready(!token.is_empty()));
+
+        Ok(tokens)
+    }
+
+    pub async fn gather_real_time_context(
+        &self,
+        recent_thoughts: Option<Vec<String>>,
+    ) -> Result<RealTimeContext, OllamaError> {
+        let thoughts_str = recent_thoughts
+            .map(|t| t.join("\n"))
+            .unwrap_or_default();
+
+        let response = match self.parser.format() {
+            ResponseFormat::Sections => {
+                let prompt = self.prompts.render(prompts::REAL_TIME_CONTEXT, &[("thoughts", &thoughts_str)]);
+                self.generate_for(RequestKind::RealTimeContext, &prompt).await?
+            }
+            ResponseFormat::Json => {
+                let prompt = self.prompts.render(prompts::REAL_TIME_CONTEXT_JSON, &[("thoughts", &thoughts_str)]);
+                self.generate_json(RequestKind::RealTimeContext, &prompt).await?
+            }
+        };
+        self.parser.parse_real_time_context(&response)
+    }
+
+    // Same analysis as gather_real_time_context, but requested as JSON. Falls
+    // back to the sectioned text format if the model can't produce valid JSON.
+    pub async fn gather_real_time_context_json(
+        &self,
+        recent_thoughts: Option<Vec<String>>,
+    ) -> Result<RealTimeContext, OllamaError> {
+        let thoughts_str = recent_thoughts
+            .as_ref()
+            .map(|t| t.join("\n"))
+            .unwrap_or_default();
+
+        let prompt = self.prompts.render(prompts::REAL_TIME_CONTEXT_JSON, &[("thoughts", &thoughts_str)]);
+
+        let response = self.generate_json(RequestKind::RealTimeContext, &prompt).await?;
+        match JsonParser.parse_real_time_context(&response) {
+            Ok(context) => Ok(context),
+            Err(e) => {
+                tracing::warn!(error = %e, "JSON real-time context unusable, retrying with text format");
+                telemetry::record_parse_failure("gather_real_time_context_json");
+                self.gather_real_time_context(recent_thoughts).await
+            }
+        }
+    }
+
+    pub async fn evaluate_dimensional_state(
+        &self,
+        position: &DimensionalPosition,
+        recent_thoughts: &[Thought],
+        recent_plans: &[Plan],
+    ) -> Result<DimensionalAdjustment, OllamaError> {
+        let recent_thoughts = recent_thoughts.iter().map(|t| t.content.clone()).collect::<Vec<_>>().join("\n");
+        let recent_plans = recent_plans.iter().map(|p| p.summary.clone()).collect::<Vec<_>>().join("\n");
+        let prompt = self.prompts.render(prompts::DIMENSIONAL_EVALUATION, &[
+            ("emergence", &format!("{:.2}", position.emergence)),
+            ("coherence", &format!("{:.2}", position.coherence)),
+            ("resilience", &format!("{:.2}", position.resilience)),
+            ("intelligence", &format!("{:.2}", position.intelligence)),
+            ("efficiency", &format!("{:.2}", position.efficiency)),
+            ("integration", &format!("{:.2}", position.integration)),
+            ("recent_thoughts", &recent_thoughts),
+            ("recent_plans", &recent_plans),
+        ]);
+
+        let response = self.generate_for(RequestKind::DimensionalEvaluation, &prompt).await?;
+
+        let values = extract_numbers(&response);
+        if values.len() < 2 {
+            tracing::warn!(response = %response, "dimensional evaluation did not contain two numbers");
+            telemetry::record_parse_failure(RequestKind::DimensionalEvaluation.label());
+            return Err(OllamaError::InvalidFormat {
+                expected: "two numbers".to_string(),
+                got: response,
+            });
+        }
+
+        Ok(DimensionalAdjustment {
+            energy: values[0].clamp(-1.0, 1.0),
+            dopamine: values[1].clamp(-1.0, 1.0),
+            raw_response: response,
+        })
+    }
+
+    fn validate_thought_content(&self, thought: &str) -> bool {
+        let lowered = thought.to_lowercase();
+        !self.prohibited_terms.iter().any(|term| lowered.contains(term.as_str()))
+            && !self.prohibited_patterns.iter().any(|pattern| pattern.is_match(thought))
+    }
+
+    fn clean_thought_content(&self, thought: &str) -> String {
+        thought.lines()
+            .filter(|line| self.validate_thought_content(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    async fn generate_thought_internal(
+        &self,
+        context: &CellContext,
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        options: &ThoughtOptions,
+    ) -> Result<(String, f64, Vec<String>, String), OllamaError> {
+        let prompt = build_thought_prompt(
+            &self.prompts,
+            context,
+            real_time_context,
+            mission,
+            &options.additional_context,
+            options.length.as_ref(),
+        );
+
+        let preset = self.options_for(RequestKind::Thought);
+        let sampling = match &options.sampling {
+            Some(sampling) => GenerateOptions {
+                temperature: sampling.temperature.or(preset.temperature),
+                num_predict: sampling.num_predict.or(preset.num_predict),
+                ..sampling.clone()
+            },
+            None => preset,
+        };
+        let started = Instant::now();
+        let response = if self.use_chat {
+            let messages = [ChatMessage::system(self.persona.as_str()), ChatMessage::user(prompt)];
+            self.send_chat(&messages, Some(&sampling)).await?
+        } else {
+            self.send_generate(&format!("{}\n\n{}", self.persona, prompt), Some(&sampling), None).await?
+        };
+        telemetry::record_method_latency(RequestKind::Thought.label(), started.elapsed());
+
+        let missing = |section: &str| OllamaError::InvalidFormat {
+            expected: format!("a {} section", section),
+            got: response.clone(),
+        };
+        let headers = &self.parser_config;
+        let (thought, rest) = split_thought(strip_code_fences(&response), headers).map_err(missing)?;
+        let thought = thought.trim().to_string();
+        
+        let relevance = match rest.lines().next().and_then(parse_unit_score) {
+            Some(relevance) => relevance,
+            None => {
+                tracing::warn!(response = %response, "thought relevance missing or unparseable, using the default");
+                telemetry::record_parse_failure(RequestKind::Thought.label());
+                DEFAULT_SCORE
+            }
+        };
+            
+        let factors = split_at_header(rest, &headers.factors)
+            .map(|(_, f)| f.lines().filter(|l| !l.trim().is_empty())
+                .map(|l| l.trim().to_string())
+                .collect())
+            .unwrap_or_default();
+
+        Ok((thought, relevance, factors, response))
+    }
+
+    pub async fn generate_contextual_thought(
+        &self,
+        context: &CellContext,
+        real_time_context: &RealTimeContext,
+        mission: &str,
+    ) -> Result<(String, f64, Vec<String>), OllamaError> {
+        let thought = self.generate_contextual_thought_with(
+            context,
+            real_time_context,
+            mission,
+            &ThoughtOptions::default(),
+        ).await?;
+        Ok((thought.content, thought.relevance, thought.factors))
+    }
+
+    // Generates thoughts back to back until the token is cancelled, which
+    // also aborts the generation in flight. Transient failures are yielded
+    // and the loop carries on; any other error ends the stream after it.
+    pub fn thought_stream<'a>(
+        &'a self,
+        context: &'a CellContext,
+        real_time_context: &'a RealTimeContext,
+        mission: &'a str,
+        token: &'a CancellationToken,
+    ) -> impl Stream<Item = Result<(String, f64, Vec<String>), OllamaError>> + 'a {
+        futures::stream::unfold(false, move |stopped| async move {
+            if stopped || token.is_cancelled() {
+                return None;
+            }
+            let thought = self.generate_contextual_thought(context, real_time_context, mission);
+            match with_cancellation(token, thought).await {
+                Ok(thought) => Some((Ok(thought), false)),
+                Err(OllamaError::Cancelled) => None,
+                Err(e) => {
+                    let stop = !e.is_transient();
+                    Some((Err(e), stop))
+                }
+            }
+        })
+    }
+
+    pub async fn generate_contextual_thought_with(
+        &self,
+        context: &CellContext,
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        options: &ThoughtOptions,
+    ) -> Result<GeneratedThought, OllamaError> {
+        if context.energy_level.is_nan() || context.energy_level <= self.min_thought_energy {
+            return Err(OllamaError::InsufficientEnergy {
+                energy: context.energy_level,
+                required: self.min_thought_energy,
+            });
+        }
+
+        let max_attempts = options.max_attempts.max(1);
+        let min_relevance = options.min_relevance.unwrap_or(0.0);
+        let fits = |thought: &GeneratedThought| options.length.is_none_or(|l| l.accepts(&thought.content));
+        // Best clean thought that fell short of min_relevance or the length
+        // bounds, kept in case no later attempt does better. One that fits
+        // beats one that doesn't, whatever their relevance.
+        let mut best: Option<GeneratedThought> = None;
+        for attempt in 1..=max_attempts {
+            if attempt > 1 {
+                telemetry::record_retry(RequestKind::Thought.label());
+            }
+            let generated = self.generate_thought_internal(
+                context, 
+                real_time_context, 
+                mission,
+                options,
+            ).await;
+            let (thought, relevance, factors, raw_response) = match generated {
+                Err(OllamaError::EmptyResponse) if attempt < max_attempts => {
+                    tracing::debug!(attempt, "empty thought response, regenerating");
+                    continue;
+                }
+                Err(OllamaError::EmptyResponse) if best.is_some() => break,
+                generated => generated?,
+            };
+
+            if self.validate_thought_content(&thought) {
+                let generated = GeneratedThought {
+                    content: thought,
+                    relevance,
+                    factors,
+                    attempts: attempt,
+                    force_cleaned: false,
+                    raw_response,
+                };
+                let fits_length = fits(&generated);
+                if fits_length && relevance >= min_relevance {
+                    return Ok(generated);
+                }
+                if fits_length {
+                    tracing::debug!(relevance, min_relevance, attempt, "thought below relevance threshold, regenerating");
+                } else {
+                    tracing::debug!(length = generated.content.len(), attempt, "thought outside length bounds, regenerating");
+                }
+                if best.as_ref().is_none_or(|b| (fits_length, generated.relevance) > (fits(b), b.relevance)) {
+                    best = Some(generated);
+                }
+                continue;
+            }
+
+            if attempt == max_attempts {
+                if let Some(best) = best {
+                    return Ok(GeneratedThought { attempts: attempt, ..best });
+                }
+                return Ok(GeneratedThought {
+                    content: self.clean_thought_content(&thought),
+                    relevance,
+                    factors,
+                    attempts: attempt,
+                    force_cleaned: true,
+                    raw_response,
+                });
+            }
+        }
+
+        best.map(|best| GeneratedThought { attempts: max_attempts, ..best })
+            .ok_or_else(|| OllamaError::Parse { context: "a valid thought".to_string() })
+    }
+
+    // Scores a thought from elsewhere the same way generated thoughts are
+    // scored, so imported and stored thoughts can be ranked together
+    pub async fn score_thought(&self, thought: &str, context: &CellContext) -> Result<f64, OllamaError> {
+        let position = &context.dimensional_position;
+        let prompt = self.prompts.render(prompts::SCORE_THOUGHT, &[
+            ("thought", &prompts::quote_user_text(thought)),
+            ("focus", &prompts::quote_user_text(&context.current_focus)),
+            ("emergence", &format!("{:.2}", position.emergence)),
+            ("coherence", &format!("{:.2}", position.coherence)),
+            ("resilience", &format!("{:.2}", position.resilience)),
+            ("intelligence", &format!("{:.2}", position.intelligence)),
+            ("efficiency", &format!("{:.2}", position.efficiency)),
+            ("integration", &format!("{:.2}", position.integration)),
+        ]);
+
+        let response = self.generate_for(RequestKind::Scoring, &prompt).await?;
+        parse_unit_score(&response).ok_or_else(|| {
+            telemetry::record_parse_failure(RequestKind::Scoring.label());
+            OllamaError::InvalidFormat { expected: "a score between 0 and 1".to_string(), got: response }
+        })
+    }
+
+    // Returns one of `categories`, as written there, or "other". A response
+    // outside the list is asked for once more before giving up.
+    pub async fn categorize_thought(&self, thought: &str, categories: &[String]) -> Result<String, OllamaError> {
+        if categories.is_empty() {
+            return Ok(OTHER_CATEGORY.to_string());
+        }
+
+        let listed = categories.iter()
+            .map(|c| format!("- {}", c))
+            .chain(std::iter::once(format!("- {}", OTHER_CATEGORY)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = self.prompts.render(prompts::CATEGORIZE_THOUGHT, &[
+            ("thought", &prompts::quote_user_text(thought)),
+            ("categories", &listed),
+        ]);
+
+        let response = self.generate_for(RequestKind::Scoring, &prompt).await?;
+        if let Some(category) = match_category(&response, categories) {
+            return Ok(category);
+        }
+
+        tracing::debug!(response = %response, "category not in the allowed list, asking again");
+        telemetry::record_retry("categorize_thought");
+        let response = self.generate_for(RequestKind::Scoring, &prompt).await?;
+        match_category(&response, categories).ok_or_else(|| {
+            telemetry::record_parse_failure("categorize_thought");
+            OllamaError::InvalidFormat {
+                expected: format!("one of: {}, {}", categories.join(", "), OTHER_CATEGORY),
+                got: response,
+            }
+        })
+    }
+
+    // Turns a finished plan into a memory entry suitable for compress_memories
+    pub async fn summarize_plan(&self, plan: &Plan) -> Result<String, OllamaError> {
+        let prompt = self.prompts.render(prompts::SUMMARIZE_PLAN, &[
+            ("summary", &plan.summary),
+            ("status", &format!("{:?}, {:.0}% complete overall", plan.status, plan.overall_completion() * 100.0)),
+            ("nodes", &format_plan_nodes(plan)),
+        ]);
+
+        let response = self.generate_for(RequestKind::Compression, &prompt).await?;
+        Ok(strip_code_fences(&response).trim().to_string())
+    }
+
+    // The reverse of thought generation: derives a mission from what a cell
+    // has been thinking about. Long histories are cut to the most relevant
+    // thoughts the same way plan prompts are.
+    pub async fn synthesize_mission(&self, thoughts: &[Thought]) -> Result<String, OllamaError> {
+        if thoughts.is_empty() {
+            return Err(OllamaError::InvalidFormat {
+                expected: "at least one thought".to_string(),
+                got: "no thoughts".to_string(),
+            });
+        }
+
+        let selected = match self.context_window {
+            Some(window) => select_by_relevance(thoughts, window / 2),
+            None => thoughts.iter().collect(),
+        };
+        let prompt = self.prompts.render(prompts::SYNTHESIZE_MISSION, &[
+            ("thoughts", &format_thought_list(&selected)),
+        ]);
+
+        let response = self.generate_for(RequestKind::Compression, &prompt).await?;
+        clean_mission(&response)
+            .map(str::to_string)
+            .ok_or(OllamaError::EmptyResponse)
+    }
+
+    // A second opinion on a plan before committing to it, beyond its score
+    pub async fn assess_plan(&self, plan: &Plan) -> Result<PlanAssessment, OllamaError> {
+        let prompt = self.prompts.render(prompts::ASSESS_PLAN, &[
+            ("summary", &plan.summary),
+            ("nodes", &format_plan_nodes(plan)),
+        ]);
+
+        let response = self.generate_for(RequestKind::Plan, &prompt).await?;
+        parse_plan_assessment(&response, &self.parser_config)
+    }
+
+    // Model-polished version of DimensionalPosition::describe_change. Skips
+    // the model when nothing moved enough to mention.
+    pub async fn describe_position_change(
+        &self,
+        before: &DimensionalPosition,
+        after: &DimensionalPosition,
+    ) -> Result<String, OllamaError> {
+        let summary = before.describe_change(after);
+        let changes = before.notable_changes(after);
+        if changes.is_empty() {
+            return Ok(summary);
+        }
+
+        let changes = changes.iter()
+            .map(|&(name, from, to)| format!("- {}: {:.1} -> {:.1}, {:+.1}", name, from, to, to - from))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = self.prompts.render(prompts::DESCRIBE_POSITION_CHANGE, &[
+            ("changes", &changes),
+            ("summary", &summary),
+        ]);
+        let response = self.generate_for(RequestKind::Compression, &prompt).await?;
+        Ok(strip_code_fences(&response).trim().to_string())
+    }
+
+    // Falls back to hierarchical compression when the memories don't fit in
+    // the configured context window.
+    pub async fn compress_memories(
+        &self,
+        memories: &[String],
+    ) -> Result<String, OllamaError> {
+        if let Some(limit) = self.context_window {
+            let total: usize = memories.iter().map(|m| estimate_tokens(m)).sum();
+            if total > limit / 2 {
+                return self.compress_memories_hierarchical(memories, limit / 2, 4).await;
+            }
+        }
+        self.compress_memory_chunk(memories).await
+    }
+
+    // Compresses only the memories closest, by embedding similarity, to what
+    // the cell is focused on, taking the most similar first until `budget`
+    // estimated tokens are used. Memories that fail to embed are left out;
+    // the chosen ones keep their original order.
+    pub async fn compress_relevant_memories(
+        &self,
+        memories: &[String],
+        context: &CellContext,
+        budget: usize,
+    ) -> Result<String, OllamaError> {
+        let query = format!(
+            "{}\nResearch topics: {}\nRecent discoveries: {}",
+            context.current_focus,
+            context.active_research_topics.join(", "),
+            context.recent_discoveries.join(", ")
+        );
+        let query_embedding = self.embed(&query).await?;
+        let embeddings = self.embed_many_with(memories, &BatchOptions::default()).await;
+
+        let mut scored = Vec::with_capacity(memories.len());
+        for (index, embedding) in embeddings.into_iter().enumerate() {
+            match embedding {
+                Ok(embedding) => scored.push((index, cosine_similarity(&query_embedding, &embedding))),
+                Err(e) => log_warning(&format!("Skipping memory {} in relevance selection: {}", index, e)),
+            }
+        }
+        if scored.is_empty() {
+            return Err(OllamaError::InvalidFormat {
+                expected: "at least one memory that could be embedded".to_string(),
+                got: format!("{} memories", memories.len()),
+            });
+        }
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let ranked: Vec<usize> = scored.iter().map(|(index, _)| *index).collect();
+        let keep = keep_within_budget(memories.len(), &ranked, |index| estimate_tokens(&memories[index]), budget);
+        let selected: Vec<String> = memories.iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(memory, _)| memory.clone())
+            .collect();
+
+        tracing::debug!(selected = selected.len(), total = memories.len(), "selected memories to compress");
+        self.compress_memories(&selected).await
+    }
+
+    async fn compress_memory_chunk(
+        &self,
+        memories: &[String],
+    ) -> Result<String, OllamaError> {
+        let prompt = self.prompts.render(prompts::COMPRESSION, &[("memories", &memories.join("\n"))]);
+
+        self.generate_for(RequestKind::Compression, &prompt).await
+    }
+
+    // Map-reduce summarization: memories are compressed in chunks that fit the
+    // token budget, then the summaries are compressed `fan_in` at a time until
+    // only one remains.
+    pub async fn compress_memories_hierarchical(
+        &self,
+        memories: &[String],
+        chunk_token_budget: usize,
+        fan_in: usize,
+    ) -> Result<String, OllamaError> {
+        if memories.is_empty() {
+            return Ok(String::new());
+        }
+
+        let mut summaries = Vec::new();
+        for chunk in chunk_by_token_budget(memories, chunk_token_budget) {
+            summaries.push(self.compress_memory_chunk(chunk).await?);
+        }
+
+        let fan_in = fan_in.max(2);
+        while summaries.len() > 1 {
+            let mut next = Vec::with_capacity(summaries.len().div_ceil(fan_in));
+            for group in summaries.chunks(fan_in) {
+                next.push(self.compress_memory_chunk(group).await?);
+            }
+            summaries = next;
+        }
+
+        Ok(summaries.remove(0))
+    }
+
+    // Compresses towards a word budget and retries once with a stricter
+    // instruction if the first summary overshoots it by more than the tolerance.
+    pub async fn compress_memories_to(
+        &self,
+        memories: &[String],
+        target: CompressionTarget,
+    ) -> Result<String, OllamaError> {
+        let input_words: usize = memories.iter().map(|m| word_count(m)).sum();
+        let target_words = target.word_budget(input_words);
+
+        let prompt = self.prompts.render(prompts::COMPRESSION_TO_BUDGET, &[
+            ("target_words", &target_words.to_string()),
+            ("memories", &memories.join("\n")),
+        ]);
+
+        let summary = self.generate_for(RequestKind::Compression, &prompt).await?;
+        let summary_words = word_count(&summary);
+        if (summary_words as f64) <= target_words as f64 * COMPRESSION_TOLERANCE {
+            return Ok(summary);
+        }
+
+        telemetry::record_retry("compress_memories_to");
+        let retry_prompt = self.prompts.render(prompts::COMPRESSION_RETRY, &[
+            ("summary_words", &summary_words.to_string()),
+            ("target_words", &target_words.to_string()),
+            ("summary", &summary),
+        ]);
+
+        self.generate_for(RequestKind::Compression, &retry_prompt).await
+    }
+
+    pub async fn create_plan(
+        &self,
+        thoughts: &[Thought],
+    ) -> Result<Plan, OllamaError> {
+        self.create_plan_with(thoughts, &PlanOptions::default()).await
+    }
+
+    // The usual think-then-plan round for one cell: `n_thoughts` varied
+    // thoughts, generated concurrently like a batch, then a plan from them.
+    // Any failed thought fails the whole call, and asking for none is an
+    // error rather than a plan from nothing.
+    pub async fn think_and_plan(
+        &self,
+        context: &CellContext,
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        n_thoughts: usize,
+    ) -> Result<(Vec<Thought>, Plan), OllamaError> {
+        if n_thoughts == 0 {
+            return Err(OllamaError::InvalidConfig("think_and_plan needs at least one thought".to_string()));
+        }
+        let options = BatchOptions { thoughts_per_cell: n_thoughts, ..Default::default() };
+        let cell = [(Uuid::new_v4(), context)];
+        let generated = self.generate_contextual_thoughts_batch_strict(&cell, real_time_context, mission, &[], &options)
+            .await?;
+
+        let thoughts: Vec<Thought> = generated.into_iter()
+            .flat_map(|(_, thoughts)| thoughts)
+            .map(|(content, relevance, factors)| Thought {
+                context_tags: vec![context.current_focus.clone()],
+                ..Thought::from_generation(content, relevance, factors)
+            })
+            .collect();
+
+        let plan = self.create_plan(&thoughts).await?;
+        Ok((thoughts, plan))
+    }
+
+    pub async fn create_plan_with(
+        &self,
+        thoughts: &[Thought],
+        options: &PlanOptions,
+    ) -> Result<Plan, OllamaError> {
+        Ok(self.create_plan_verbose(thoughts, options).await?.0)
+    }
+
+    fn plan_thought_list(&self, thoughts: &[Thought], options: &PlanOptions) -> String {
+        let budget = options.thought_token_budget.or(self.context_window.map(|w| w / 2));
+        let selected = match budget {
+            Some(budget) => select_by_relevance(thoughts, budget),
+            None => thoughts.iter().collect(),
+        };
+        if selected.len() < thoughts.len() {
+            tracing::warn!(
+                kept = selected.len(),
+                dropped = thoughts.len() - selected.len(),
+                budget = ?budget,
+                "plan thoughts exceed the token budget, keeping the most relevant"
+            );
+        }
+        format_thought_list(&selected)
+    }
+
+    fn plan_prompt(&self, thoughts: &[Thought], options: &PlanOptions, format: ResponseFormat) -> String {
+        let template = match format {
+            ResponseFormat::Sections => prompts::PLAN,
+            ResponseFormat::Json => prompts::PLAN_JSON,
+        };
+        self.prompts.render(template, &[
+            ("thoughts", &self.plan_thought_list(thoughts, options)),
+            ("node_format", options.node_format()),
+            ("node_shape", options.node_shape()),
+            ("node_count", &options.node_count()),
+        ])
+    }
+
+    // Also returns the model's raw text, for comparing against what was parsed
+    pub async fn create_plan_verbose(
+        &self,
+        thoughts: &[Thought],
+        options: &PlanOptions,
+    ) -> Result<(Plan, String), OllamaError> {
+        let format = self.parser.format();
+        let prompt = self.plan_prompt(thoughts, options, format);
+        let response = match format {
+            ResponseFormat::Sections => self.generate_for(RequestKind::Plan, &prompt).await?,
+            ResponseFormat::Json => self.generate_json(RequestKind::Plan, &prompt).await?,
+        };
+
+        // An unreadable response still gets the reformat retry and defaults
+        let parsed = self.parser.parse_plan(&response, options).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "plan response could not be parsed");
+            telemetry::record_parse_failure(RequestKind::Plan.label());
+            ParsedPlan::default()
+        });
+
+        let plan = self.finish_plan(thoughts, parsed, &response, options).await;
+        Ok((plan, response))
+    }
+
+    // Streams the plan as the model writes it: each node is sent once its
+    // line is complete, then the assembled plan. Errors end the stream.
+    // Incremental parsing needs the sectioned format, so this ignores the
+    // configured response parser.
+    pub fn create_plan_stream(
+        &self,
+        thoughts: &[Thought],
+        options: &PlanOptions,
+    ) -> ReceiverStream<Result<PlanStreamEvent, OllamaError>> {
+        let (sender, receiver) = mpsc::channel(PLAN_STREAM_BUFFER);
+        let client = self.clone();
+        let thoughts = thoughts.to_vec();
+        let options = options.clone();
+
+        tokio::spawn(async move {
+            let prompt = client.plan_prompt(&thoughts, &options, ResponseFormat::Sections);
+            let preset = client.options_for(RequestKind::Plan);
+            let tokens = match client.send_generate_stream(&prompt, Some(&preset)).await {
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            futures::pin_mut!(tokens);
+
+            let mut parser = PlanTextParser::new(&options, &client.parser_config);
+            let mut response = String::new();
+            while let Some(token) = tokens.next().await {
+                let nodes = match token {
+                    Ok(token) => {
+                        response.push_str(&token);
+                        parser.push(&token)
+                    }
+                    Err(e) => {
+                        let _ = sender.send(Err(e)).await;
+                        return;
+                    }
+                };
+                for node in nodes {
+                    if sender.send(Ok(PlanStreamEvent::Node(node))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+
+            for node in parser.finish() {
+                if sender.send(Ok(PlanStreamEvent::Node(node))).await.is_err() {
+                    return;
+                }
+            }
+            let plan = client.finish_plan(&thoughts, parser.into_parsed(), &response, &options).await;
+            let _ = sender.send(Ok(PlanStreamEvent::Plan(plan))).await;
+        });
+
+        ReceiverStream::new(receiver)
+    }
+
+    // Turns parsed plan text into the final plan, filling in defaults and
+    // running the optional ranking pass. A response with no readable nodes
+    // gets one chance to be reformatted by the model before default nodes
+    // stand in for it.
+    async fn finish_plan(
+        &self,
+        thoughts: &[Thought],
+        mut parsed: ParsedPlan,
+        response: &str,
+        options: &PlanOptions,
+    ) -> Plan {
+        if parsed.nodes.is_empty() {
+            match self.reformat_plan_response(response, options).await {
+                Ok(reformatted) if !reformatted.nodes.is_empty() => parsed = reformatted,
+                Ok(_) => tracing::warn!("reformatted plan still has no readable nodes"),
+                Err(e) => tracing::warn!(error = %e, "plan reformat request failed"),
+            }
+        }
+
+        let summary = parsed.summary
+            .unwrap_or_else(|| String::from("Plan based on collected thoughts"));
+        let score = parsed.score.unwrap_or_else(|| {
+            tracing::warn!("plan score missing or unparseable, using the default");
+            telemetry::record_parse_failure(RequestKind::Plan.label());
+            DEFAULT_SCORE
+        });
+
+        let mut plan = assemble_plan(thoughts, summary, parsed.nodes, score, options);
+        if options.rank_nodes {
+            // The plan is usable without weights, so a failed ranking only costs them
+            if let Err(e) = self.rank_plan_nodes(&mut plan).await {
+                tracing::warn!(error = %e, "node ranking failed, keeping default importance");
+            }
+        }
+        plan
+    }
+
+    // Shows the model its own malformed plan and asks for it in the expected
+    // format
+    async fn reformat_plan_response(
+        &self,
+        response: &str,
+        options: &PlanOptions,
+    ) -> Result<ParsedPlan, OllamaError> {
+        tracing::warn!("no plan nodes parsed, asking the model to reformat its response");
+        telemetry::record_retry(RequestKind::Plan.label());
+
+        let prompt = self.prompts.render(prompts::PLAN_REFORMAT, &[
+            ("response", response.trim()),
+            ("node_format", options.node_format()),
+        ]);
+        let reformatted = self.generate_for(RequestKind::Plan, &prompt).await?;
+        SectionParser::with_config(self.parser_config.clone()).parse_plan(&reformatted, options)
+    }
+
+    // Asks the model how central each node is and stores it as the node's
+    // importance. Nodes the model skips keep their current importance.
+    pub async fn rank_plan_nodes(&self, plan: &mut Plan) -> Result<(), OllamaError> {
+        let nodes = plan.nodes.iter()
+            .enumerate()
+            .map(|(i, n)| format!("{}. {}: {}", i + 1, n.title, n.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = self.prompts.render(prompts::RANK_NODES, &[
+            ("summary", &plan.summary),
+            ("nodes", &nodes),
+        ]);
+
+        let response = self.generate_for(RequestKind::Plan, &prompt).await?;
+        let weights = parse_node_weights(&response, plan.nodes.len());
+        if weights.iter().all(Option::is_none) {
+            telemetry::record_parse_failure("rank_plan_nodes");
+            return Err(OllamaError::InvalidFormat { expected: "numbered node importances".to_string(), got: response });
+        }
+
+        for (node, weight) in plan.nodes.iter_mut().zip(weights) {
+            if let Some(weight) = weight {
+                node.importance = weight;
+            }
+        }
+        Ok(())
+    }
+
+    // JSON-mode counterpart of create_plan_with. Models that ignore the format
+    // constraint fall back to the pipe-delimited text parser.
+    pub async fn create_plan_json(
+        &self,
+        thoughts: &[Thought],
+        options: &PlanOptions,
+    ) -> Result<Plan, OllamaError> {
+        let prompt = self.plan_prompt(thoughts, options, ResponseFormat::Json);
+        let response = self.generate_json(RequestKind::Plan, &prompt).await?;
+        let parsed = match JsonParser.parse_plan(&response, options) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                tracing::warn!(error = %e, response = %response, "JSON plan unusable, retrying with text format");
+                telemetry::record_parse_failure("create_plan_json");
+                return self.create_plan_with(thoughts, options).await;
+            }
+        };
+
+        Ok(self.finish_plan(thoughts, parsed, &response, options).await)
+    }
+
+    // Proposes a replacement for a failed node from the rest of the plan.
+    // The replacement keeps the original's id and dependencies so it can be
+    // swapped in without rewiring dependants. Any other status is refused,
+    // since replacing the node would throw away its progress.
+    pub async fn regenerate_node(&self, plan: &Plan, node_id: Uuid) -> Result<PlanNode, OllamaError> {
+        let failed = plan.nodes.iter()
+            .find(|n| n.id == node_id)
+            .ok_or(PlanError::NodeNotFound(node_id))?;
+        if failed.status != PlanNodeStatus::Failed {
+            return Err(PlanError::NodeNotFailed { node: node_id, status: failed.status.clone() }.into());
+        }
+
+        let other_nodes = plan.nodes.iter()
+            .filter(|n| n.id != node_id)
+            .map(|n| format!("- {} ({:?}): {}", n.title, n.status, n.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = self.prompts.render(prompts::REGENERATE_NODE, &[
+            ("summary", &plan.summary),
+            ("other_nodes", if other_nodes.is_empty() { "- none" } else { &other_nodes }),
+            ("title", &failed.title),
+            ("status", &format!("{:?}", failed.status)),
+            ("description", &failed.description),
+        ]);
+
+        let response = self.generate_for(RequestKind::Plan, &prompt).await?;
+        let (node, _) = strip_code_fences(&response)
+            .lines()
+            .find_map(parse_plan_node)
+            .ok_or_else(|| OllamaError::Parse { context: "a replacement plan node".to_string() })?;
+
+        Ok(PlanNode {
+            id: failed.id,
+            dependencies: failed.dependencies.clone(),
+            estimated_completion: Score::MIN,
+            ..node
+        })
+    }
+
+    // Breaks a coarse node into 2-4 sub-nodes that run in sequence. The first
+    // inherits the original's dependencies, so the chain can stand in for it.
+    pub async fn decompose_node(&self, node: &PlanNode) -> Result<Vec<PlanNode>, OllamaError> {
+        let prompt = self.prompts.render(prompts::DECOMPOSE_NODE, &[
+            ("title", &node.title),
+            ("description", &node.description),
+        ]);
+
+        let response = self.generate_for(RequestKind::Plan, &prompt).await?;
+        let lines: Vec<&str> = strip_code_fences(&response)
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        let mut sub_nodes: Vec<PlanNode> = merge_node_lines(&lines)
+            .iter()
+            .filter_map(|line| parse_plan_node(line))
+            .map(|(sub_node, _)| sub_node)
+            .take(4)
+            .collect();
+
+        if sub_nodes.len() < 2 {
+            return Err(OllamaError::InvalidFormat {
+                expected: "2-4 sub-nodes".to_string(),
+                got: response,
+            });
+        }
+
+        let mut previous = None;
+        for sub_node in &mut sub_nodes {
+            sub_node.estimated_completion = Score::MIN;
+            sub_node.dependencies = match previous {
+                Some(id) => vec![id],
+                None => node.dependencies.clone(),
+            };
+            previous = Some(sub_node.id);
+        }
+
+        Ok(sub_nodes)
+    }
+
+    // Collected per cell so one failing or slow cell doesn't cost the rest of
+    // the batch. A cell is Ok when at least one of its thoughts came back.
+    // Returned as a Vec in the order the cells were given, like the strict
+    // form, rather than as a map keyed by cell; collect it into a HashMap
+    // where lookups by id are wanted.
+    pub async fn generate_contextual_thoughts_batch(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Vec<(Uuid, Result<Vec<(String, f64, Vec<String>)>, OllamaError>)> {
+        let requests = self.batch_thought_requests(cell_contexts, real_time_context, mission, additional_context, options);
+        let mut outcomes: Vec<_> = futures::stream::iter(requests)
+            .buffer_unordered(options.max_concurrency.max(1))
+            .collect()
+            .await;
+        outcomes.sort_by_key(|(position, _, _)| *position);
+
+        // A cell's requests are adjacent once sorted, so each groups into the
+        // last entry. Later failures for a cell that already has a thought are
+        // only logged.
+        let mut results: Vec<(Uuid, Result<Vec<_>, OllamaError>)> = Vec::new();
+        for (_, cell_id, outcome) in outcomes {
+            let slot = match results.last_mut() {
+                Some((last_id, slot)) if *last_id == cell_id => Some(slot),
+                _ => None,
+            };
+            match (outcome, slot) {
+                (Ok(thought), Some(Ok(thoughts))) => thoughts.push((thought.content, thought.relevance, thought.factors)),
+                (Err(e), Some(Ok(_))) => log_warning(&format!("Thought generation failed for cell {}: {}", cell_id, e)),
+                (outcome, slot) => {
+                    if let Err(e) = &outcome {
+                        log_warning(&format!("Thought generation failed for cell {}: {}", cell_id, e));
+                    }
+                    let outcome = outcome.map(|thought| vec![(thought.content, thought.relevance, thought.factors)]);
+                    match slot {
+                        Some(slot) => *slot = outcome,
+                        None => results.push((cell_id, outcome)),
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    // Fail-fast form of generate_contextual_thoughts_batch: the first error
+    // drops the requests still in flight and is returned as is.
+    pub async fn generate_contextual_thoughts_batch_strict(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Result<Vec<(Uuid, Vec<(String, f64, Vec<String>)>)>, OllamaError> {
+        let requests = self.batch_thought_requests(cell_contexts, real_time_context, mission, additional_context, options);
+        let mut outcomes: Vec<_> = futures::stream::iter(requests)
+            .buffer_unordered(options.max_concurrency.max(1))
+            .map(|(position, cell_id, outcome)| outcome.map(|thought| (position, cell_id, thought)))
+            .try_collect()
+            .await?;
+
+        // Completion order depends on which request finishes first, so restore
+        // input order before grouping
+        outcomes.sort_by_key(|(position, _, _)| *position);
+
+        let mut results: Vec<(Uuid, Vec<_>)> = Vec::new();
+        for (_, cell_id, thought) in outcomes {
+            let entry = (thought.content, thought.relevance, thought.factors);
+            match results.last_mut() {
+                Some((last_id, thoughts)) if *last_id == cell_id => thoughts.push(entry),
+                _ => results.push((cell_id, vec![entry])),
+            }
+        }
+
+        Ok(results)
+    }
+
+    // Built up front rather than mapped lazily over the stream so the
+    // returned futures stay Send
+    fn batch_thought_requests<'a>(
+        &'a self,
+        cell_contexts: &'a [(Uuid, &'a CellContext)],
+        real_time_context: &'a RealTimeContext,
+        mission: &'a str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Vec<impl Future<Output = (usize, Uuid, Result<GeneratedThought, OllamaError>)> + 'a> {
+        let per_cell = options.thoughts_per_cell.max(1);
+        let cell_timeout = options.cell_timeout;
+
+        cell_contexts.iter()
+            .flat_map(|(cell_id, context)| (0..per_cell).map(move |index| (*cell_id, *context, index)))
+            .enumerate()
+            .map(|(position, (cell_id, context, index))| {
+                let thought_options = ThoughtOptions {
+                    additional_context: additional_context.to_vec(),
+                    sampling: (per_cell > 1).then(|| GenerateOptions {
+                        temperature: Some(DIVERSE_BASE_TEMPERATURE + DIVERSE_TEMPERATURE_STEP * index as f64),
+                        seed: Some(index as i64),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                async move {
+                    let request = self.generate_contextual_thought_with(
+                        context,
+                        real_time_context,
+                        mission,
+                        &thought_options,
+                    );
+                    let outcome = match cell_timeout {
+                        Some(limit) => tokio::time::timeout(limit, request)
+                            .await
+                            .unwrap_or(Err(OllamaError::RequestTimeout)),
+                        None => request.await,
+                    };
+                    (position, cell_id, outcome)
+                }
+            })
+            .collect()
+    }
+
+    pub async fn query_llm(&self, prompt: &str) -> Result<String, OllamaError> {
+        self.generate(prompt).await
+    }
+}
+
+// Inherent methods take precedence over trait methods, so each of these
+// forwards to the OllamaClient method of the same name.
+#[async_trait]
+impl LanguageModel for OllamaClient {
+    type Error = OllamaError;
 
-impl OllamaClient {
-    pub fn new(model: String) -> Result<Self, Box<dyn Error>> {
-        Ok(Self {
-            client: Client::new(),
-            model,
-        })
+    async fn generate(&self, prompt: &str) -> Result<String, OllamaError> {
+        OllamaClient::generate(self, prompt).await
     }
 
-    async fn generate(&self, prompt: &str) -> Result<String, Box<dyn Error>> {
-        let request = GenerateRequest {
-            model: self.model.clone(),
-            prompt: prompt.to_string(),
-            stream: false,
-        };
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, OllamaError> {
+        OllamaClient::embed(self, text).await
+    }
 
-        let response = self.client
-            .post(format!("{}/generate", OLLAMA_API_URL))
-            .json(&request)
-            .send()
-            .await?
-            .json::<GenerateResponse>()
-            .await?;
+    async fn generate_contextual_thought(
+        &self,
+        context: &CellContext,
+        real_time_context: &RealTimeContext,
+        mission: &str,
+    ) -> Result<(String, f64, Vec<String>), OllamaError> {
+        OllamaClient::generate_contextual_thought(self, context, real_time_context, mission).await
+    }
 
-        Ok(response.response)
+    async fn generate_contextual_thoughts_batch(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Vec<(Uuid, Result<Vec<(String, f64, Vec<String>)>, OllamaError>)> {
+        OllamaClient::generate_contextual_thoughts_batch(
+            self,
+            cell_contexts,
+            real_time_context,
+            mission,
+            additional_context,
+            options,
+        ).await
     }
 
-    pub async fn gather_real_time_context(
+    async fn generate_contextual_thoughts_batch_strict(
+        &self,
+        cell_contexts: &[(Uuid, &CellContext)],
+        real_time_context: &RealTimeContext,
+        mission: &str,
+        additional_context: &[String],
+        options: &BatchOptions,
+    ) -> Result<Vec<(Uuid, Vec<(String, f64, Vec<String>)>)>, OllamaError> {
+        OllamaClient::generate_contextual_thoughts_batch_strict(
+            self,
+            cell_contexts,
+            real_time_context,
+            mission,
+            additional_context,
+            options,
+        ).await
+    }
+
+    async fn gather_real_time_context(
         &self,
         recent_thoughts: Option<Vec<String>>,
-    ) -> Result<RealTimeContext, Box<dyn Error>> {
-        let thoughts_str = recent_thoughts
-            .map(|t| t.join("\n"))
-            .unwrap_or_default();
+    ) -> Result<RealTimeContext, OllamaError> {
+        OllamaClient::gather_real_time_context(self, recent_thoughts).await
+    }
 
-        let prompt = format!(
-            "Based on these recent thoughts, generate a real-time context analysis.
-            Thoughts:
-            {}
-
-            Respond in this exact format:
-            MARKET_TRENDS:
-            [trend1]
-            [trend2]
-            TECH_DEVELOPMENTS:
-            [dev1]
-            [dev2]
-            CURRENT_EVENTS:
-            [event1]
-            [event2]
-            USER_INTERACTIONS:
-            [interaction1]
-            [interaction2]",
-            thoughts_str
-        );
+    async fn evaluate_dimensional_state(
+        &self,
+        position: &DimensionalPosition,
+        recent_thoughts: &[Thought],
+        recent_plans: &[Plan],
+    ) -> Result<DimensionalAdjustment, OllamaError> {
+        OllamaClient::evaluate_dimensional_state(self, position, recent_thoughts, recent_plans).await
+    }
 
-        let response = self.generate(&prompt).await?;
-        let mut market_trends = Vec::new();
-        let mut tech_developments = Vec::new();
-        let mut current_events = Vec::new();
-        let mut user_interactions = Vec::new();
-        
-        let mut current_section = "";
-        
-        for line in response.lines() {
-            match line.trim() {
-                "MARKET_TRENDS:" => current_section = "market",
-                "TECH_DEVELOPMENTS:" => current_section = "tech",
-                "CURRENT_EVENTS:" => current_section = "events",
-                "USER_INTERACTIONS:" => current_section = "interactions",
-                "" => continue,
-                line => {
-                    match current_section {
-                        "market" => market_trends.push(line.to_string()),
-                        "tech" => tech_developments.push(line.to_string()),
-                        "events" => current_events.push(line.to_string()),
-                        "interactions" => user_interactions.push(line.to_string()),
-                        _ => {}
-                    }
-                }
+    async fn create_plan_with(
+        &self,
+        thoughts: &[Thought],
+        options: &PlanOptions,
+    ) -> Result<Plan, OllamaError> {
+        OllamaClient::create_plan_with(self, thoughts, options).await
+    }
+
+    async fn compress_memories(&self, memories: &[String]) -> Result<String, OllamaError> {
+        OllamaClient::compress_memories(self, memories).await
+    }
+
+    async fn score_thought(&self, thought: &str, context: &CellContext) -> Result<f64, OllamaError> {
+        OllamaClient::score_thought(self, thought, context).await
+    }
+}
+
+// Races any client call against the token. Dropping the request future
+// aborts the HTTP request, so once cancelled nothing else runs.
+pub async fn with_cancellation<T, F>(token: &CancellationToken, request: F) -> Result<T, OllamaError>
+where
+    F: Future<Output = Result<T, OllamaError>>,
+{
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(OllamaError::Cancelled),
+        result = request => result,
+    }
+}
+
+// Spherical k-means: assigns each vector to the centroid it is most
+// cosine-similar to. Seeding picks the first vector, then repeatedly the one
+// least similar to every centroid so far, so results are deterministic.
+// Returns one cluster index per vector; k is clamped to 1..=vectors.len().
+fn kmeans(vectors: &[Vec<f32>], k: usize) -> Vec<usize> {
+    if vectors.is_empty() {
+        return Vec::new();
+    }
+    let k = k.clamp(1, vectors.len());
+
+    let mut centroids = vec![vectors[0].clone()];
+    while centroids.len() < k {
+        let farthest = (0..vectors.len())
+            .min_by(|&a, &b| {
+                let closest = |i: usize| centroids.iter().map(|c| cosine_similarity(c, &vectors[i])).fold(f64::MIN, f64::max);
+                closest(a).total_cmp(&closest(b))
+            })
+            .unwrap_or(0);
+        centroids.push(vectors[farthest].clone());
+    }
+
+    // Ties go to the lower cluster index
+    let nearest = |vector: &[f32], centroids: &[Vec<f32>]| {
+        (0..centroids.len())
+            .max_by(|&a, &b| {
+                let similarity = |i: usize| cosine_similarity(&centroids[i], vector);
+                similarity(a).total_cmp(&similarity(b)).then(b.cmp(&a))
+            })
+            .unwrap_or(0)
+    };
+
+    let mut assignments: Vec<usize> = vectors.iter().map(|v| nearest(v, &centroids)).collect();
+    for _ in 0..MAX_KMEANS_ITERATIONS {
+        // An emptied cluster keeps its old centroid
+        for (cluster, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<&Vec<f32>> = vectors.iter()
+                .zip(&assignments)
+                .filter(|(_, assigned)| **assigned == cluster)
+                .map(|(v, _)| v)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            for (i, value) in centroid.iter_mut().enumerate() {
+                *value = members.iter().map(|m| m.get(i).copied().unwrap_or(0.0)).sum::<f32>() / members.len() as f32;
             }
         }
 
-        Ok(RealTimeContext {
-            timestamp: Utc::now(),
-            market_trends,
-            current_events,
-            technological_developments: tech_developments,
-            user_interactions,
-            environmental_data: HashMap::new(),
-            mission_progress: Vec::new(),
-        })
+        let updated: Vec<usize> = vectors.iter().map(|v| nearest(v, &centroids)).collect();
+        if updated == assignments {
+            break;
+        }
+        assignments = updated;
+    }
+    assignments
+}
+
+// 0.0 for mismatched lengths or zero vectors
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
     }
 
-    pub async fn evaluate_dimensional_state(
-        &self,
-        position: &DimensionalPosition,
-        recent_thoughts: &[Thought],
-        recent_plans: &[Plan],
-    ) -> Result<(f64, f64), Box<dyn Error>> {
-        let prompt = format!(
-            "Evaluate this cell's dimensional state and suggest energy and dopamine adjustments.
-            Current dimensions:
-            - Emergence: {:.2}
-            - Coherence: {:.2}
-            - Resilience: {:.2}
-            - Intelligence: {:.2}
-            - Efficiency: {:.2}
-            - Integration: {:.2}
-
-            Recent thoughts: {}
-            Recent plans: {}
-
-            Respond ONLY with two numbers separated by a comma:
-            [energy_adjustment],
-            [dopamine_adjustment]
-            ",
-            position.emergence,
-            position.coherence,
-            position.resilience,
-            position.intelligence,
-            position.efficiency,
-            position.integration,
-            recent_thoughts.iter().map(|t| t.content.clone()).collect::<Vec<_>>().join("\n"),
-            recent_plans.iter().map(|p| p.summary.clone()).collect::<Vec<_>>().join("\n")
-        );
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (*x as f64, *y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
 
-        let response = self.generate(&prompt).await?;
-        let values: Vec<f64> = response
-            .split(',')
-            .filter_map(|s| s.trim().parse().ok())
-            .collect();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+fn build_thought_prompt(
+    templates: &PromptTemplates,
+    context: &CellContext,
+    real_time_context: &RealTimeContext,
+    mission: &str,
+    additional_context: &[String],
+    length: Option<&ThoughtLength>,
+) -> String {
+    let additional = if additional_context.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nAdditional considerations:\n- {}", additional_context.join("\n- "))
+    };
+    let length = length.map(|l| format!("\n5. {}", l.instruction())).unwrap_or_default();
+    let position = &context.dimensional_position;
+
+    templates.render(prompts::THOUGHT, &[
+        ("mission", &prompts::quote_user_text(mission)),
+        ("focus", &prompts::quote_user_text(&context.current_focus)),
+        ("evolution_stage", &context.evolution_stage.to_string()),
+        ("energy", &format!("{:.2}", context.energy_level)),
+        ("emergence", &format!("{:.2}", position.emergence)),
+        ("coherence", &format!("{:.2}", position.coherence)),
+        ("resilience", &format!("{:.2}", position.resilience)),
+        ("intelligence", &format!("{:.2}", position.intelligence)),
+        ("efficiency", &format!("{:.2}", position.efficiency)),
+        ("integration", &format!("{:.2}", position.integration)),
+        ("market_trends", &real_time_context.market_trends.join(", ")),
+        ("tech_developments", &real_time_context.technological_developments.join(", ")),
+        ("current_events", &real_time_context.current_events.iter().map(Event::to_string).collect::<Vec<_>>().join(", ")),
+        ("additional_context", &additional),
+        ("length", &length),
+    ])
+}
+
+// Splits a thought response into the thought text and whatever follows the
+// relevance header. The rest of the thought header's own line is thought text
+// even if it mentions relevance. Errors with the header that was missing.
+fn split_thought<'a, 'h>(response: &'a str, headers: &'h ParserConfig) -> Result<(&'a str, &'a str), &'h str> {
+    let (_, body) = split_at_header(response, &headers.thought).ok_or(headers.thought.as_str())?;
+    let first_line = body.find('\n').map_or(body.len(), |end| end + 1);
+    let (tail, rest) = split_at_header(&body[first_line..], &headers.relevance)
+        .ok_or(headers.relevance.as_str())?;
+    Ok((&body[..first_line + tail.len()], rest))
+}
+
+// The mission statement without fences, quotes or a "Mission:" label in any
+// case, or None if nothing is left
+fn clean_mission(response: &str) -> Option<&str> {
+    let mission = strip_code_fences(response).trim();
+    let mission = strip_header(mission, "Mission:").unwrap_or(mission);
+    let mission = mission.trim().trim_matches('"').trim();
+    (!mission.is_empty()).then_some(mission)
+}
+
+// Matches a category answer against the allowed list, ignoring case, list
+// markers, a "Category:" label, quotes and trailing punctuation
+fn match_category(response: &str, categories: &[String]) -> Option<String> {
+    let answer = strip_list_marker(strip_code_fences(response).lines().find(|l| !l.trim().is_empty())?);
+    let answer = strip_header(answer, "Category:").unwrap_or(answer);
+    let answer = answer.trim().trim_matches(|c: char| matches!(c, '"' | '\'' | '`' | '*' | '.' | '!')).trim();
 
-        if values.len() != 2 {
-            return Err("Invalid response format from model".into());
+    categories.iter()
+        .find(|c| c.trim().eq_ignore_ascii_case(answer))
+        .cloned()
+        .or_else(|| answer.eq_ignore_ascii_case(OTHER_CATEGORY).then(|| OTHER_CATEGORY.to_string()))
+}
+
+// Counts runs of text ending in . ! or ? followed by whitespace (or the end
+// of the text), so "2.5" doesn't split and a trailing fragment without
+// punctuation still counts as a sentence
+fn count_sentences(text: &str) -> usize {
+    let mut count = 0;
+    let mut has_words = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        has_words |= c.is_alphanumeric();
+        let ends_sentence = matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|next| next.is_whitespace());
+        if ends_sentence && has_words {
+            count += 1;
+            has_words = false;
         }
+    }
+    count + has_words as usize
+}
 
-        Ok((values[0], values[1]))
+fn format_thought_list(thoughts: &[&Thought]) -> String {
+    thoughts.iter()
+        .map(|t| format!("- {}", t.content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Keeps the most relevant thoughts whose content fits the token budget, in
+// their original order. The most relevant thought is always kept.
+fn select_by_relevance(thoughts: &[Thought], budget: usize) -> Vec<&Thought> {
+    let mut ranked: Vec<usize> = (0..thoughts.len()).collect();
+    ranked.sort_by(|&a, &b| thoughts[b].relevance_score.value().total_cmp(&thoughts[a].relevance_score.value()));
+
+    let keep = keep_within_budget(thoughts.len(), &ranked, |index| estimate_tokens(&thoughts[index].content), budget);
+    thoughts.iter().zip(keep).filter(|(_, keep)| *keep).map(|(t, _)| t).collect()
+}
+
+// Walks `ranked` (indices into `len` items, best first) and keeps each item
+// whose estimated tokens still fit the budget; smaller items further down can
+// fill the gaps. The first item is always kept so the selection isn't empty.
+fn keep_within_budget(len: usize, ranked: &[usize], cost: impl Fn(usize) -> usize, budget: usize) -> Vec<bool> {
+    let mut used = 0;
+    let mut keep = vec![false; len];
+    for &index in ranked {
+        let cost = cost(index);
+        if used + cost > budget && used > 0 {
+            continue;
+        }
+        used += cost;
+        keep[index] = true;
     }
+    keep
+}
 
-    fn validate_thought_content(thought: &str) -> bool {
-        let prohibited_terms = [
-            "energy level",
-            "evolution stage",
-            "dimensional position",
-            "emergence:",
-            "coherence:",
-            "resilience:",
-            "intelligence:",
-            "efficiency:",
-            "integration:",
-        ];
+// Untagged names resolve to `:latest` on the server
+fn model_matches(available: &str, wanted: &str) -> bool {
+    available == wanted
+        || (!wanted.contains(':') && available.strip_suffix(":latest") == Some(wanted))
+}
 
-        !prohibited_terms.iter().any(|term| 
-            thought.to_lowercase().contains(&term.to_lowercase())
-        )
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, OllamaError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
     }
 
-    fn clean_thought_content(&self, thought: &str) -> String {
-        thought.lines()
-            .filter(|line| Self::validate_thought_content(line))
-            .collect::<Vec<_>>()
-            .join("\n")
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorResponse>(&body)
+        .map(|e| e.error)
+        .unwrap_or(body);
+
+    Err(OllamaError::Server { status: status.as_u16(), message })
+}
+
+// Splits a newline-delimited JSON body into one decoded item per line.
+fn ndjson_stream<T, S, B>(body: S) -> impl Stream<Item = Result<T, OllamaError>>
+where
+    T: DeserializeOwned,
+    S: Stream<Item = reqwest::Result<B>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    futures::stream::unfold(
+        (body, Vec::<u8>::new(), false),
+        |(mut body, mut buffer, mut finished)| async move {
+            loop {
+                if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=pos).collect();
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+                    let item = serde_json::from_slice(&line).map_err(OllamaError::from);
+                    return Some((item, (body, buffer, finished)));
+                }
+
+                if finished {
+                    if buffer.iter().all(u8::is_ascii_whitespace) {
+                        return None;
+                    }
+                    let line = std::mem::take(&mut buffer);
+                    let item = serde_json::from_slice(&line).map_err(OllamaError::from);
+                    return Some((item, (body, buffer, finished)));
+                }
+
+                match body.next().await {
+                    Some(Ok(bytes)) => buffer.extend_from_slice(bytes.as_ref()),
+                    Some(Err(e)) => {
+                        buffer.clear();
+                        return Some((Err(e.into()), (body, buffer, true)));
+                    }
+                    None => finished = true,
+                }
+            }
+        },
+    )
+}
+
+fn build_http_client(connect_timeout: Duration, request_timeout: Duration) -> Result<Client, OllamaError> {
+    Ok(Client::builder()
+        .user_agent(DEFAULT_USER_AGENT)
+        .connect_timeout(connect_timeout)
+        .timeout(request_timeout)
+        .build()?)
+}
+
+fn validate_base_url(base_url: &str) -> Result<String, OllamaError> {
+    let trimmed = base_url.trim().trim_end_matches('/');
+    let url = reqwest::Url::parse(trimmed)
+        .map_err(|e| OllamaError::InvalidUrl(format!("'{}': {}", base_url, e)))?;
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(OllamaError::InvalidUrl(format!("'{}': scheme must be http or https", base_url)));
+    }
+    if url.host_str().is_none() {
+        return Err(OllamaError::InvalidUrl(format!("'{}': missing host", base_url)));
     }
 
-    async fn generate_thought_internal(
-        &self,
-        context: &CellContext,
-        real_time_context: &RealTimeContext,
-        mission: &str,
-    ) -> Result<(String, f64, Vec<String>), Box<dyn Error>> {
-        let prompt = format!(
-            "You are an AI system focused on developing innovative collaboration approaches.
-            Your task is to generate an insightful thought about AI collaboration systems.
-            
-            Context (for consideration but do not repeat in response):
-            - Mission: {}
-            - Focus Area: {}
-            - System Stage: Evolution Stage {}
-            - Energy/Resources: {:.2}
-            - Dimensional Analysis: [E:{:.2} C:{:.2} R:{:.2} I:{:.2} Ef:{:.2} In:{:.2}]
-            
-            Environmental Context:
-            - Market: {}
-            - Technology: {}
-            - Events: {}
-            
-            Instructions:
-            1. Generate a focused thought about improving AI collaboration
-            2. Do not mention system state values (energy, stages, etc.)
-            3. Focus on insights, strategies, and observations
-            4. Stay concise and actionable
-            
-            Format your response exactly as follows:
-            THOUGHT:
-            [Your thought content without mentioning system state]
-            RELEVANCE:
-            [Score between 0-1]
-            FACTORS:
-            [Key factor 1]
-            [Key factor 2]
-            [Key factor 3]",
-            mission,
-            context.current_focus,
-            context.evolution_stage,
-            context.energy_level,
-            context.dimensional_position.emergence,
-            context.dimensional_position.coherence,
-            context.dimensional_position.resilience,
-            context.dimensional_position.intelligence,
-            context.dimensional_position.efficiency,
-            context.dimensional_position.integration,
-            real_time_context.market_trends.join(", "),
-            real_time_context.technological_developments.join(", "),
-            real_time_context.current_events.join(", ")
-        );
+    Ok(trimmed.to_string())
+}
 
-        let response = self.generate(&prompt).await?;
-        let mut sections = response.split("THOUGHT:").nth(1).ok_or("Invalid response")?
-            .split("RELEVANCE:");
-        
-        let thought = sections.next().ok_or("Missing thought")?.trim().to_string();
-        let rest = sections.next().ok_or("Missing relevance")?;
-        
-        let relevance = rest.lines()
-            .next()
-            .and_then(|s| s.trim().parse().ok())
-            .ok_or("Invalid relevance score")?;
-            
-        let factors = rest.split("FACTORS:")
-            .nth(1)
-            .map(|f| f.lines().filter(|l| !l.trim().is_empty())
-                .map(|l| l.trim().to_string())
+fn validate_model_name(model: &str) -> Result<String, OllamaError> {
+    let trimmed = model.trim();
+    if trimmed.is_empty() {
+        return Err(OllamaError::InvalidConfig("model name must not be empty".to_string()));
+    }
+    if trimmed.chars().any(char::is_whitespace) {
+        return Err(OllamaError::InvalidConfig(format!("model name '{}' must not contain whitespace", trimmed)));
+    }
+    if !trimmed.contains(':') {
+        tracing::warn!(model = trimmed, "model name has no tag, Ollama will resolve it to :latest");
+    }
+
+    Ok(trimmed.to_string())
+}
+
+// Rough chars/4 heuristic; close enough for English text with most tokenizers
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count() / 4
+}
+
+// Groups consecutive items so each group stays within the budget; an item
+// that is larger than the budget on its own gets a group to itself.
+fn chunk_by_token_budget(items: &[String], budget: usize) -> Vec<&[String]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut tokens = 0;
+
+    for (i, item) in items.iter().enumerate() {
+        let item_tokens = estimate_tokens(item);
+        if i > start && tokens + item_tokens > budget {
+            chunks.push(&items[start..i]);
+            start = i;
+            tokens = 0;
+        }
+        tokens += item_tokens;
+    }
+    if start < items.len() {
+        chunks.push(&items[start..]);
+    }
+
+    chunks
+}
+
+fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+fn parse_key_value(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim_start_matches(['-', '*']).trim();
+    let value = value.trim();
+
+    if key.is_empty() || value.is_empty() {
+        return None;
+    }
+
+    Some((key.to_string(), value.to_string()))
+}
+
+// Returns the node along with the 1-based numbers of the nodes it depends on,
+// taken from an optional fourth field. The completion estimate may be a
+// decimal or a percentage and defaults to 0.0 when missing.
+fn parse_plan_node(line: &str) -> Option<(PlanNode, Vec<usize>)> {
+    let parts: Vec<&str> = strip_list_marker(line).split('|').collect();
+    if parts.len() >= 2 {
+        let title = parts[0].trim();
+        let description = parts[1].trim();
+        let completion = parts.get(2).and_then(|c| parse_fraction(c)).unwrap_or(0.0);
+        let dependencies = parts.get(3)
+            .map(|deps| extract_numbers(deps)
+                .into_iter()
+                .filter(|n| *n >= 1.0 && n.fract() == 0.0)
+                .map(|n| n as usize)
                 .collect())
             .unwrap_or_default();
+        
+        if !title.is_empty() && !description.is_empty() {
+            let node = PlanNode {
+                id: Uuid::new_v4(),
+                title: title.to_string(),
+                description: description.to_string(),
+                status: PlanNodeStatus::Pending,
+                importance: DEFAULT_NODE_IMPORTANCE,
+                estimated_completion: Score::from(completion),
+                dependencies: Vec::new(),
+            };
+            return Some((node, dependencies));
+        }
+    }
+    None
+}
+
+// Folds continuation lines into the node they belong to and returns one
+// pipe-delimited line per node. A line starts a new node when it is numbered,
+// or when it carries fields and either has a bullet or follows a node that
+// already has its title, description and completion.
+fn merge_node_lines(lines: &[&str]) -> Vec<String> {
+    let mut merged: Vec<Vec<String>> = Vec::new();
+
+    for &line in lines {
+        let unmarked = strip_list_marker(line);
+        let marked = unmarked.len() != line.trim_start().len();
+        let numbered = marked && line.trim_start().starts_with(|c: char| c.is_ascii_digit());
+        let has_fields = unmarked.contains('|');
+
+        let mut pieces = unmarked.split('|').map(|p| p.trim().to_string());
+        let parts = match merged.last_mut() {
+            Some(parts) if !numbered && !(has_fields && (marked || parts.len() >= 3)) => parts,
+            _ => {
+                merged.push(pieces.collect());
+                continue;
+            }
+        };
 
-        Ok((thought, relevance, factors))
+        if !has_fields {
+            // Free text extends the description, wherever it appears
+            if parts.len() < 2 {
+                parts.push(String::new());
+            }
+            append_text(&mut parts[1], line.trim());
+            continue;
+        }
+
+        let first = pieces.next().unwrap_or_default();
+        if parts.len() >= 2 {
+            let last = parts.len() - 1;
+            append_text(&mut parts[last], &first);
+        } else {
+            parts.push(first);
+        }
+        parts.extend(pieces);
     }
 
-    pub async fn generate_contextual_thought(
-        &self,
-        context: &CellContext,
-        real_time_context: &RealTimeContext,
-        mission: &str,
-    ) -> Result<(String, f64, Vec<String>), Box<dyn Error>> {
-        for attempt in 0..3 {  // Allow up to 3 attempts
-            let (thought, relevance, factors) = self.generate_thought_internal(
-                context, 
-                real_time_context, 
-                mission
-            ).await?;
+    merged.into_iter().map(|parts| parts.join(" | ")).collect()
+}
+
+fn append_text(target: &mut String, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if !target.is_empty() {
+        target.push(' ');
+    }
+    target.push_str(text);
+}
 
-            if Self::validate_thought_content(&thought) {
-                return Ok((thought, relevance, factors));
+// Only references to earlier nodes are kept, so the resulting graph can't
+// contain cycles.
+fn link_node_dependencies(nodes: &mut [PlanNode], dependencies: &[Vec<usize>]) {
+    let ids: Vec<Uuid> = nodes.iter().map(|n| n.id).collect();
+
+    for (index, (node, deps)) in nodes.iter_mut().zip(dependencies).enumerate() {
+        for &number in deps {
+            if number == 0 || number > index {
+                continue;
+            }
+            let id = ids[number - 1];
+            if !node.dependencies.contains(&id) {
+                node.dependencies.push(id);
             }
+        }
+    }
+}
 
-            if attempt == 2 {  // Last attempt
-                let cleaned_thought = self.clean_thought_content(&thought);
-                return Ok((cleaned_thought, relevance, factors));
+// Reads "N: weight" lines into one slot per node; out-of-range numbers are ignored
+fn parse_node_weights(response: &str, node_count: usize) -> Vec<Option<f64>> {
+    let mut weights = vec![None; node_count];
+    for line in strip_code_fences(response).lines() {
+        let Some((number, weight)) = strip_list_marker(line).split_once(':') else {
+            continue;
+        };
+        let index = number.trim().trim_start_matches('#').parse::<usize>().ok();
+        if let (Some(index @ 1..), Some(weight)) = (index, parse_unit_score(weight)) {
+            if let Some(slot) = weights.get_mut(index - 1) {
+                *slot = Some(weight);
             }
         }
-        
-        Err("Failed to generate valid thought".into())
     }
+    weights
+}
 
-    pub async fn compress_memories(
-        &self,
-        memories: &[String],
-    ) -> Result<String, Box<dyn Error>> {
-        let prompt = format!(
-            "Compress these memories into a single cohesive summary:
-            {}
-            
-            Respond with ONLY the compressed summary.",
-            memories.join("\n")
-        );
+fn default_plan_score() -> f64 {
+    DEFAULT_SCORE
+}
+
+// A missing confidence falls back to the default score, but a response with
+// none of the three sections is rejected. A header may share its line with
+// the section's first item, as in "CONFIDENCE: 0.8".
+fn parse_plan_assessment(response: &str, headers: &ParserConfig) -> Result<PlanAssessment, OllamaError> {
+    let sections = [
+        (&headers.confidence, "confidence"),
+        (&headers.risks, "risks"),
+        (&headers.assumptions, "assumptions"),
+    ];
+    let mut confidence = None;
+    let mut risks = Vec::new();
+    let mut assumptions = Vec::new();
+    let mut section = "";
+    let mut seen_section = false;
+
+    for line in strip_code_fences(response).lines() {
+        let mut item = line;
+        let header = sections.iter()
+            .find_map(|(header, name)| strip_header(line, header).map(|rest| (rest, *name)));
+        if let Some((rest, name)) = header {
+            section = name;
+            seen_section = true;
+            item = rest;
+        }
 
-        self.generate(&prompt).await
+        let item = strip_list_marker(item).trim();
+        if item.is_empty() || item.eq_ignore_ascii_case("none") {
+            continue;
+        }
+        match section {
+            "confidence" if confidence.is_none() => {
+                confidence = parse_fraction(item).map(|c| c.clamp(0.0, 1.0)).or_else(|| parse_unit_score(item));
+            }
+            "risks" => risks.push(item.to_string()),
+            "assumptions" => assumptions.push(item.to_string()),
+            _ => {}
+        }
     }
 
-    pub async fn create_plan(
-        &self,
-        thoughts: &[Thought],
-    ) -> Result<Plan, Box<dyn Error>> {
-        let prompt = format!(
-            "Based on these thoughts, create a detailed strategic plan.
-            
-            Thoughts for consideration:
-            {}
-            
-            Instructions:
-            1. Create a clear plan summary
-            2. Generate at least 3 actionable plan nodes
-            3. Each node must have a title, description, and completion estimate (0-1)
-            4. Assign a relevance score to the overall plan
-            
-            Respond in exactly this format:
-            SUMMARY:
-            [Write a clear 1-2 sentence plan summary]
-            
-            NODES:
-            1. [Node Title] | [Detailed description of the node's objective and approach] | [Completion estimate between 0-1]
-            2. [Node Title] | [Detailed description of the node's objective and approach] | [Completion estimate between 0-1]
-            3. [Node Title] | [Detailed description of the node's objective and approach] | [Completion estimate between 0-1]
-            
-            SCORE:
-            [Overall plan score between 0-1]",
-            thoughts.iter()
-                .map(|t| format!("- {}", t.content))
-                .collect::<Vec<_>>()
-                .join("\n")
-        );
+    if !seen_section {
+        telemetry::record_parse_failure("assess_plan");
+        return Err(OllamaError::InvalidFormat {
+            expected: format!("{} {} and {} sections", headers.confidence, headers.risks, headers.assumptions),
+            got: response.to_string(),
+        });
+    }
 
-        let response = self.generate(&prompt).await?;
-        
-        // Initialize with default values
-        let mut summary = String::from("Plan based on collected thoughts");
-        let mut nodes = Vec::new();
-        let mut score = 0.5;  // Default score
+    let confidence = confidence.unwrap_or_else(|| {
+        tracing::warn!(response = %response, "plan confidence missing or unparseable, using the default");
+        telemetry::record_parse_failure("assess_plan");
+        DEFAULT_SCORE
+    });
+    Ok(PlanAssessment { confidence, risks, assumptions })
+}
+
+fn format_plan_nodes(plan: &Plan) -> String {
+    plan.nodes.iter()
+        .map(|n| format!(
+            "- {} ({:?}, {:.0}%): {}",
+            n.title,
+            n.status,
+            n.estimated_completion.value() * 100.0,
+            n.description
+        ))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// First number in the text, clamped into 0.0..=1.0
+fn parse_unit_score(text: &str) -> Option<f64> {
+    extract_numbers(text)
+        .into_iter()
+        .find(|n| n.is_finite())
+        .map(|n| n.clamp(0.0, 1.0))
+}
+
+// Reads the SUMMARY: / NODES: style the default prompts ask for. Never
+// fails; whatever can't be read is left for the caller's defaults.
+#[derive(Clone, Debug, Default)]
+pub struct SectionParser {
+    headers: ParserConfig,
+}
+
+impl SectionParser {
+    pub fn with_config(headers: ParserConfig) -> Self {
+        Self { headers }
+    }
+}
+
+impl ResponseParser for SectionParser {
+    fn parse_plan(&self, response: &str, options: &PlanOptions) -> Result<ParsedPlan, OllamaError> {
+        let mut parser = PlanTextParser::new(options, &self.headers);
+        parser.push(strip_code_fences(response));
+        parser.finish();
+        Ok(parser.into_parsed())
+    }
+
+    fn parse_real_time_context(&self, response: &str) -> Result<RealTimeContext, OllamaError> {
+        let mut context = RealTimeContext::default();
+        let mut current_section = "";
+        let sections = [
+            (&self.headers.market_trends, "market"),
+            (&self.headers.tech_developments, "tech"),
+            (&self.headers.current_events, "events"),
+            (&self.headers.user_interactions, "interactions"),
+            (&self.headers.environmental_data, "environment"),
+            (&self.headers.mission_progress, "progress"),
+        ];
 
-        let mut current_section = "";
-        for line in response.lines() {
+        for line in strip_code_fences(response).lines() {
+            if let Some((_, section)) = sections.iter().find(|(header, _)| header_matches(line, header)) {
+                current_section = section;
+                continue;
+            }
             match line.trim() {
-                "SUMMARY:" => current_section = "summary",
-                "NODES:" => current_section = "nodes",
-                "SCORE:" => current_section = "score",
                 "" => continue,
                 line => {
                     match current_section {
-                        "summary" => {
-                            if !line.is_empty() {
-                                summary = line.to_string();
-                            }
-                        },
-                        "nodes" => {
-                            if let Some(node) = parse_plan_node(line) {
-                                nodes.push(node);
-                            }
-                        },
-                        "score" => {
-                            if let Ok(s) = line.trim().parse() {
-                                score = s;
+                        "market" => context.market_trends.push(line.to_string()),
+                        "tech" => context.technological_developments.push(line.to_string()),
+                        "events" => context.current_events.push(Event::parse(line)),
+                        "interactions" => context.user_interactions.push(line.to_string()),
+                        "environment" => {
+                            if let Some((key, value)) = parse_key_value(line) {
+                                context.environmental_data.insert(key, EnvValue::parse(&value));
                             }
                         },
+                        "progress" => context.mission_progress.push(line.to_string()),
                         _ => {}
                     }
                 }
             }
         }
 
-        // If no nodes were created, generate default nodes
-        if nodes.is_empty() {
-            nodes = generate_default_nodes(&summary);
+        Ok(context)
+    }
+}
+
+// Reads the JSON shapes the *_JSON prompts describe. Malformed JSON is an
+// error rather than an empty result.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonParser;
+
+impl ResponseParser for JsonParser {
+    fn format(&self) -> ResponseFormat {
+        ResponseFormat::Json
+    }
+
+    fn parse_plan(&self, response: &str, options: &PlanOptions) -> Result<ParsedPlan, OllamaError> {
+        let parsed: PlanJson = parse_json_lenient(response)?;
+
+        let mut nodes = Vec::new();
+        let mut node_dependencies = Vec::new();
+        for node in parsed.nodes {
+            let title = node.title.trim();
+            let description = node.description.trim();
+            if title.is_empty() || description.is_empty() {
+                continue;
+            }
+            nodes.push(PlanNode {
+                id: Uuid::new_v4(),
+                title: title.to_string(),
+                description: description.to_string(),
+                status: PlanNodeStatus::Pending,
+                importance: DEFAULT_NODE_IMPORTANCE,
+                estimated_completion: Score::from(node.estimated_completion),
+                dependencies: Vec::new(),
+            });
+            node_dependencies.push(node.depends_on);
         }
 
-        // Ensure we have at least 3 nodes
-        while nodes.len() < 3 {
-            nodes.push(create_default_node(nodes.len() + 1));
+        if options.infer_dependencies {
+            link_node_dependencies(&mut nodes, &node_dependencies);
         }
 
-        Ok(Plan {
-            id: Uuid::new_v4(),
-            summary,
-            nodes,
-            thoughts: thoughts.to_vec(),
-            score,
-            participating_cells: Vec::new(),
-            created_at: Utc::now(),
-            status: PlanStatus::Proposed,
+        let summary = Some(parsed.summary.trim().to_string()).filter(|s| !s.is_empty());
+        Ok(ParsedPlan { summary, nodes, score: Some(parsed.score.clamp(0.0, 1.0)) })
+    }
+
+    fn parse_real_time_context(&self, response: &str) -> Result<RealTimeContext, OllamaError> {
+        let parsed: RealTimeContextJson = parse_json_lenient(response)?;
+        Ok(RealTimeContext {
+            timestamp: Utc::now(),
+            market_trends: parsed.market_trends,
+            current_events: parsed.current_events.iter().map(|e| Event::parse(e)).collect(),
+            technological_developments: parsed.technological_developments,
+            user_interactions: parsed.user_interactions,
+            environmental_data: parsed.environmental_data
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::Number(n) => (key, n.as_f64().map_or_else(|| EnvValue::Text(n.to_string()), EnvValue::Number)),
+                    serde_json::Value::Bool(b) => (key, EnvValue::Bool(b)),
+                    serde_json::Value::String(s) => (key, EnvValue::parse(&s)),
+                    other => (key, EnvValue::Text(other.to_string())),
+                })
+                .collect(),
+            mission_progress: parsed.mission_progress,
         })
     }
+}
 
-    pub async fn generate_contextual_thoughts_batch(
-        &self,
-        cell_contexts: &[(Uuid, &CellContext)],
-        real_time_context: &RealTimeContext,
-        mission: &str,
-        additional_context: &[String],
-    ) -> Result<HashMap<Uuid, Vec<(String, f64, Vec<String>)>>, Box<dyn Error>> {
-        let mut results = HashMap::new();
+// Incremental parser for the SUMMARY / NODES / SCORE plan format. Text may
+// arrive in arbitrary pieces; a node is only parsed once the following line
+// shows it is complete, so streamed and whole responses parse the same way.
+struct PlanTextParser {
+    headers: ParserConfig,
+    infer_dependencies: bool,
+    keep_model_completion: bool,
+    pending: String,
+    section: &'static str,
+    summary: Option<String>,
+    node_lines: Vec<String>,
+    parsed_lines: usize,
+    nodes: Vec<PlanNode>,
+    dependencies: Vec<Vec<usize>>,
+    score: Option<f64>,
+}
 
-        for (cell_id, context) in cell_contexts {
-            let (thought, relevance, factors) = self.generate_contextual_thought(
-                context,
-                real_time_context,
-                mission
-            ).await?;
+impl PlanTextParser {
+    fn new(options: &PlanOptions, headers: &ParserConfig) -> Self {
+        Self {
+            headers: headers.clone(),
+            infer_dependencies: options.infer_dependencies,
+            keep_model_completion: options.keep_model_completion,
+            pending: String::new(),
+            section: "",
+            summary: None,
+            node_lines: Vec::new(),
+            parsed_lines: 0,
+            nodes: Vec::new(),
+            dependencies: Vec::new(),
+            score: None,
+        }
+    }
 
-            results.insert(*cell_id, vec![(thought, relevance, factors)]);
+    // Feeds more model output and returns the nodes it completed
+    fn push(&mut self, text: &str) -> Vec<PlanNode> {
+        // A CRLF split across pushes only adds a blank line, which is skipped
+        self.pending.push_str(&normalize_text(text));
+        let mut completed = Vec::new();
+        while let Some(end) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=end).collect();
+            completed.extend(self.process_line(&line));
         }
+        completed
+    }
 
-        Ok(results)
+    // Parses whatever is left once the output has ended
+    fn finish(&mut self) -> Vec<PlanNode> {
+        let rest = std::mem::take(&mut self.pending);
+        let mut completed = self.process_line(&rest);
+        completed.extend(self.take_nodes(true));
+        completed
     }
 
-    pub async fn query_llm(&self, prompt: &str) -> Result<String, Box<dyn Error>> {
-        self.generate(prompt).await
+    fn into_parsed(self) -> ParsedPlan {
+        ParsedPlan { summary: self.summary, nodes: self.nodes, score: self.score }
+    }
+
+    fn process_line(&mut self, line: &str) -> Vec<PlanNode> {
+        let section = match line.trim() {
+            line if header_matches(line, &self.headers.summary) => "summary",
+            line if header_matches(line, &self.headers.nodes) => "nodes",
+            line if header_matches(line, &self.headers.score) => "score",
+            line if line.is_empty() || line.starts_with("```") => return Vec::new(),
+            line => {
+                match self.section {
+                    "summary" => self.summary = Some(line.to_string()),
+                    "nodes" => {
+                        self.node_lines.push(line.to_string());
+                        return self.take_nodes(false);
+                    },
+                    "score" => {
+                        if self.score.is_none() {
+                            self.score = parse_unit_score(line);
+                        }
+                    },
+                    _ => {}
+                }
+                return Vec::new();
+            }
+        };
+
+        // Leaving the node list means its last node is complete
+        let completed = if self.section == "nodes" { self.take_nodes(true) } else { Vec::new() };
+        self.section = section;
+        completed
+    }
+
+    // Parses merged node lines that can no longer grow. The last one is held
+    // back unless `all` is set, since a continuation line may still follow.
+    fn take_nodes(&mut self, all: bool) -> Vec<PlanNode> {
+        let lines: Vec<&str> = self.node_lines.iter().map(String::as_str).collect();
+        let merged = merge_node_lines(&lines);
+        let ready = if all { merged.len() } else { merged.len().saturating_sub(1) };
+
+        let mut completed = Vec::new();
+        for text in merged.iter().take(ready).skip(self.parsed_lines) {
+            if let Some((mut node, dependencies)) = parse_plan_node(text) {
+                if !self.keep_model_completion {
+                    node.estimated_completion = Score::MIN;
+                }
+                self.nodes.push(node);
+                self.dependencies.push(dependencies);
+                if self.infer_dependencies {
+                    link_node_dependencies(&mut self.nodes, &self.dependencies);
+                }
+                completed.extend(self.nodes.last().cloned());
+            }
+        }
+        self.parsed_lines = self.parsed_lines.max(ready);
+        completed
     }
 }
 
-fn parse_plan_node(line: &str) -> Option<PlanNode> {
-    let parts: Vec<&str> = line.split('|').collect();
-    if parts.len() >= 3 {
-        let title = parts[0].trim();
-        let description = parts[1].trim();
-        let completion: f64 = parts[2].trim().parse().unwrap_or(0.0);
-        
-        if !title.is_empty() && !description.is_empty() {
-            return Some(PlanNode {
-                id: Uuid::new_v4(),
-                title: title.to_string(),
-                description: description.to_string(),
-                status: PlanNodeStatus::Pending,
-                estimated_completion: completion.clamp(0.0, 1.0),
-                dependencies: Vec::new(),
-            });
+fn assemble_plan(
+    thoughts: &[Thought],
+    summary: String,
+    mut nodes: Vec<PlanNode>,
+    score: f64,
+    options: &PlanOptions,
+) -> Plan {
+    // If no nodes were created, generate default nodes
+    if nodes.is_empty() {
+        tracing::warn!("no plan nodes parsed, falling back to default nodes");
+        telemetry::record_parse_failure(RequestKind::Plan.label());
+        nodes = generate_default_nodes(&summary);
+    }
+
+    // Before truncating and padding, so duplicates don't crowd out real nodes
+    let nodes_before = nodes.len();
+    let mut nodes = dedupe_plan_nodes(nodes);
+    if nodes.len() < nodes_before {
+        tracing::warn!(removed = nodes_before - nodes.len(), "dropped duplicate plan nodes");
+    }
+
+    if let Some(max) = options.max_nodes.map(|max| max.max(options.min_nodes)) {
+        if nodes.len() > max {
+            tracing::warn!(parsed = nodes.len(), max, "truncating plan to the maximum node count");
+            nodes.truncate(max);
+            let kept: Vec<Uuid> = nodes.iter().map(|n| n.id).collect();
+            for node in &mut nodes {
+                node.dependencies.retain(|id| kept.contains(id));
+            }
         }
     }
-    None
+
+    if !options.keep_model_completion {
+        for node in &mut nodes {
+            node.estimated_completion = Score::MIN;
+        }
+    }
+
+    if nodes.len() < options.min_nodes {
+        tracing::warn!(parsed = nodes.len(), "padding plan with default nodes");
+    }
+    while nodes.len() < options.min_nodes {
+        nodes.push(create_default_node(nodes.len() + 1));
+    }
+
+    Plan {
+        id: Uuid::new_v4(),
+        summary,
+        nodes,
+        thoughts: thoughts.to_vec(),
+        score: Score::from(score),
+        participating_cells: Vec::new(),
+        created_at: Utc::now(),
+        status: PlanStatus::Proposed,
+    }
+}
+
+// Collapses nodes whose titles match once case, spacing and punctuation are
+// ignored. The first node keeps its place and id but takes the longer
+// description; dependencies on a dropped duplicate move to the survivor.
+fn dedupe_plan_nodes(nodes: Vec<PlanNode>) -> Vec<PlanNode> {
+    fn title_key(title: &str) -> String {
+        title.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    let mut kept: Vec<PlanNode> = Vec::with_capacity(nodes.len());
+    let mut remapped: HashMap<Uuid, Uuid> = HashMap::new();
+    for node in nodes {
+        let key = title_key(&node.title);
+        match kept.iter_mut().find(|k| title_key(&k.title) == key) {
+            Some(existing) => {
+                if node.description.trim().len() > existing.description.trim().len() {
+                    existing.description = node.description;
+                }
+                for dependency in node.dependencies {
+                    if !existing.dependencies.contains(&dependency) {
+                        existing.dependencies.push(dependency);
+                    }
+                }
+                remapped.insert(node.id, existing.id);
+            }
+            None => kept.push(node),
+        }
+    }
+
+    // A duplicate's dependencies can point past the survivor's position;
+    // only earlier nodes are kept so the plan stays acyclic, matching
+    // link_node_dependencies
+    let positions: HashMap<Uuid, usize> = kept.iter().enumerate().map(|(index, n)| (n.id, index)).collect();
+    for (index, node) in kept.iter_mut().enumerate() {
+        let mut dependencies = Vec::new();
+        for dependency in node.dependencies.drain(..) {
+            let dependency = *remapped.get(&dependency).unwrap_or(&dependency);
+            let earlier = positions.get(&dependency).is_none_or(|&position| position < index);
+            if earlier && !dependencies.contains(&dependency) {
+                dependencies.push(dependency);
+            }
+        }
+        node.dependencies = dependencies;
+    }
+    kept
 }
 
 fn generate_default_nodes(summary: &str) -> Vec<PlanNode> {
@@ -452,7 +3900,8 @@ fn generate_default_nodes(summary: &str) -> Vec<PlanNode> {
             description: format!("Analyze current state and requirements for: {}", summary
         ),
         status: PlanNodeStatus::Pending,
-        estimated_completion: 0.0,
+        importance: DEFAULT_NODE_IMPORTANCE,
+        estimated_completion: Score::MIN,
         dependencies: Vec::new(),
     },
     PlanNode {
@@ -460,7 +3909,8 @@ fn generate_default_nodes(summary: &str) -> Vec<PlanNode> {
         title: "Implementation Strategy".to_string(),
         description: "Develop detailed implementation approach based on initial analysis".to_string(),
         status: PlanNodeStatus::Pending,
-        estimated_completion: 0.0,
+        importance: DEFAULT_NODE_IMPORTANCE,
+        estimated_completion: Score::MIN,
         dependencies: Vec::new(),
     },
     PlanNode {
@@ -468,7 +3918,8 @@ fn generate_default_nodes(summary: &str) -> Vec<PlanNode> {
         title: "Validation and Review".to_string(),
         description: "Review implementation results and validate against objectives".to_string(),
         status: PlanNodeStatus::Pending,
-        estimated_completion: 0.0,
+        importance: DEFAULT_NODE_IMPORTANCE,
+        estimated_completion: Score::MIN,
         dependencies: Vec::new(),
     },
 ]
@@ -480,7 +3931,706 @@ PlanNode {
     title: format!("Phase {}", index),
     description: format!("Execute phase {} of the plan", index),
     status: PlanNodeStatus::Pending,
-    estimated_completion: 0.0,
+    importance: DEFAULT_NODE_IMPORTANCE,
+    estimated_completion: Score::MIN,
     dependencies: Vec::new(),
 }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock::cell_context;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Stands in for the Ollama server: records each request body and answers
+    // it with `respond` after `delay`, one connection per request. Also notes
+    // the most requests it was ever handling at once.
+    struct StubServer {
+        url: String,
+        requests: Arc<Mutex<Vec<Value>>>,
+        peak_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl StubServer {
+        fn requests(&self) -> Vec<Value> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        fn client(&self, model: &str) -> OllamaClient {
+            OllamaClient::new(model.to_string()).unwrap().with_base_url(&self.url).unwrap()
+        }
+    }
+
+    async fn stub_server<F>(delay: Duration, respond: F) -> StubServer
+    where
+        F: Fn(&Value) -> (u16, Value) + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let peak_in_flight = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let respond = Arc::new(respond);
+
+        let (recorded, peak) = (requests.clone(), peak_in_flight.clone());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (recorded, peak, in_flight, respond) =
+                    (recorded.clone(), peak.clone(), in_flight.clone(), respond.clone());
+                tokio::spawn(async move {
+                    let body = read_request_body(&mut socket).await;
+                    recorded.lock().unwrap().push(body.clone());
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                    let (status, reply) = respond(&body);
+                    let reply = reply.to_string();
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        reply.len(),
+                        reply
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        StubServer { url, requests, peak_in_flight }
+    }
+
+    // Answers successive requests with each text in turn, repeating the last
+    fn replies(texts: &[&str]) -> impl Fn(&Value) -> (u16, Value) + Send + Sync + 'static {
+        let texts: Vec<String> = texts.iter().map(|t| t.to_string()).collect();
+        let next = AtomicUsize::new(0);
+        move |_| {
+            let index = next.fetch_add(1, Ordering::SeqCst).min(texts.len() - 1);
+            (200, json!({ "response": texts[index] }))
+        }
+    }
+
+    async fn read_request_body(socket: &mut tokio::net::TcpStream) -> Value {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 4096];
+        while let Ok(read) = socket.read(&mut chunk).await {
+            if read == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else { continue };
+            let head = String::from_utf8_lossy(&buffer[..end]).to_ascii_lowercase();
+            let length: usize = head.lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0);
+            let body = end + 4;
+            if buffer.len() >= body + length {
+                return serde_json::from_slice(&buffer[body..body + length]).unwrap_or(Value::Null);
+            }
+        }
+        Value::Null
+    }
+
+    #[test]
+    fn test_base_url_is_validated_up_front() {
+        assert_eq!(validate_base_url(" http://ollama.internal:11434/api/ ").unwrap(), "http://ollama.internal:11434/api");
+        assert!(matches!(validate_base_url("ollama.internal:11434"), Err(OllamaError::InvalidUrl(_))));
+        assert!(matches!(validate_base_url("ftp://ollama.internal/api"), Err(OllamaError::InvalidUrl(_))));
+        assert!(matches!(validate_base_url("not a url"), Err(OllamaError::InvalidUrl(_))));
+
+        let client = OllamaClient::builder(DEFAULT_MODEL).base_url("https://ollama.example.com/api/").build().unwrap();
+        assert_eq!(client.base_url, "https://ollama.example.com/api");
+        assert!(OllamaClient::builder(DEFAULT_MODEL).base_url("localhost").build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_slow_requests_fail_with_a_timeout_error() {
+        let server = stub_server(Duration::from_millis(500), |_| (200, json!({ "response": "late" }))).await;
+        let client = server.client("primary")
+            .with_timeouts(Duration::from_secs(1), Duration::from_millis(100))
+            .unwrap();
+        assert!(matches!(client.generate("hello").await, Err(OllamaError::RequestTimeout)));
+
+        let zero = OllamaClient::builder(DEFAULT_MODEL).request_timeout(Duration::ZERO).build();
+        assert!(matches!(zero, Err(OllamaError::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_repeated_deterministic_requests() {
+        let server = stub_server(Duration::ZERO, |_| (200, json!({ "response": "cached" }))).await;
+        let client = server.client("primary").with_cache(2);
+        let exact = GenerateOptions { temperature: Some(0.0), ..Default::default() };
+
+        assert_eq!(client.generate_with_options("same", &exact).await.unwrap(), "cached");
+        assert_eq!(client.generate_with_options("same", &exact).await.unwrap(), "cached");
+        assert_eq!(server.requests().len(), 1);
+
+        // Model and options are part of the key
+        client.generate_with_model_options("other", "same", &exact).await.unwrap();
+        let shorter = GenerateOptions { num_predict: Some(8), ..exact.clone() };
+        client.generate_with_options("same", &shorter).await.unwrap();
+        assert_eq!(server.requests().len(), 3);
+
+        // With room for two, the first entry was evicted by the other two
+        client.generate_with_options("same", &exact).await.unwrap();
+        assert_eq!(server.requests().len(), 4);
+
+        let sampled = GenerateOptions { temperature: Some(0.8), ..Default::default() };
+        client.generate_with_options("same", &sampled).await.unwrap();
+        client.generate_with_options("same", &sampled).await.unwrap();
+        assert_eq!(server.requests().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_moves_on_only_for_unavailable_models() {
+        let server = stub_server(Duration::ZERO, |body| match body["model"].as_str() {
+            Some("primary") => (404, json!({ "error": "model 'primary' not found" })),
+            Some("backup") => (200, json!({ "response": "from backup" })),
+            _ => (500, json!({ "error": "unexpected model" })),
+        }).await;
+        let client = server.client("primary")
+            .with_fallback_model("backup").unwrap()
+            .with_fallback_model("last").unwrap();
+
+        let generation = client.generate_with_fallback("hello").await.unwrap();
+        assert_eq!(generation.model, "backup");
+        assert_eq!(generation.text, "from backup");
+        let models: Vec<Value> = server.requests().iter().map(|r| r["model"].clone()).collect();
+        assert_eq!(models, [json!("primary"), json!("backup")]);
+
+        let server = stub_server(Duration::ZERO, |_| (400, json!({ "error": "invalid options" }))).await;
+        let client = server.client("primary").with_fallback_model("backup").unwrap();
+        let failed = client.generate_with_fallback("hello").await;
+        assert!(matches!(failed, Err(OllamaError::Server { status: 400, .. })));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_limit_throttles_concurrent_calls() {
+        let server = stub_server(Duration::from_millis(50), |_| (200, json!({ "response": "done" }))).await;
+        let client = server.client("primary").with_max_in_flight(2).unwrap();
+
+        let calls: Vec<_> = (0..6).map(|_| client.generate("hello")).collect();
+        let results = future::join_all(calls).await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(server.requests().len(), 6);
+        assert_eq!(server.peak_in_flight.load(Ordering::SeqCst), 2);
+
+        assert!(matches!(server.client("primary").with_max_in_flight(0), Err(OllamaError::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_empty_responses_are_reported_and_regenerated() {
+        let server = stub_server(Duration::ZERO, replies(&[" \n "])).await;
+        assert!(matches!(server.client("primary").generate("hello").await, Err(OllamaError::EmptyResponse)));
+
+        let server = stub_server(Duration::ZERO, replies(&["", "THOUGHT: Share idle compute\nRELEVANCE: 0.9"])).await;
+        let thought = server.client("primary")
+            .generate_contextual_thought_with(&cell_context(50.0), &RealTimeContext::default(), "mission", &ThoughtOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(thought.content, "Share idle compute");
+        assert_eq!(thought.attempts, 2);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_low_relevance_thoughts_are_regenerated_keeping_the_best() {
+        let options = ThoughtOptions { max_attempts: 3, min_relevance: Some(0.7), ..Default::default() };
+        let context = cell_context(50.0);
+        let rtc = RealTimeContext::default();
+
+        let server = stub_server(Duration::ZERO, replies(&[
+            "THOUGHT: weak\nRELEVANCE: 0.3",
+            "THOUGHT: strong\nRELEVANCE: 0.8",
+        ])).await;
+        let thought = server.client("primary").generate_contextual_thought_with(&context, &rtc, "mission", &options).await.unwrap();
+        assert_eq!((thought.content.as_str(), thought.attempts), ("strong", 2));
+
+        let server = stub_server(Duration::ZERO, replies(&[
+            "THOUGHT: weak\nRELEVANCE: 0.3",
+            "THOUGHT: closer\nRELEVANCE: 0.5",
+            "THOUGHT: weaker\nRELEVANCE: 0.4",
+        ])).await;
+        let thought = server.client("primary").generate_contextual_thought_with(&context, &rtc, "mission", &options).await.unwrap();
+        assert_eq!((thought.content.as_str(), thought.attempts), ("closer", 3));
+        assert_eq!(thought.relevance, 0.5);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fim_reports_models_that_cannot_infill() {
+        let server = stub_server(Duration::ZERO, |body| match body["model"].as_str() {
+            Some("coder") => (200, json!({ "response": "    return a + b;" })),
+            _ => (400, json!({ "error": "registry.ollama.ai/library/llama3:latest does not support insert" })),
+        }).await;
+
+        let infill = server.client("coder").generate_fim("fn add(a: i32, b: i32) -> i32 {\n", "\n}").await.unwrap();
+        assert_eq!(infill, "    return a + b;");
+        let request = &server.requests()[0];
+        assert_eq!(request["suffix"], "\n}");
+        assert_eq!(request["options"]["num_predict"], 256);
+
+        let refused = server.client("llama3").generate_fim("fn add(", ")").await;
+        assert!(matches!(refused, Err(OllamaError::InsertUnsupported(model)) if model == "llama3"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_and_streaming_fall_back_too() {
+        let server = stub_server(Duration::ZERO, |body| match body["model"].as_str() {
+            Some("backup") if body.get("messages").is_some() => {
+                (200, json!({ "message": { "role": "assistant", "content": "chatted" } }))
+            }
+            Some("backup") => (200, json!({ "response": "streamed", "done": true })),
+            _ => (404, json!({ "error": "model not found" })),
+        }).await;
+        let client = server.client("primary").with_fallback_model("backup").unwrap();
+
+        assert_eq!(client.chat(&[ChatMessage::user("hello")]).await.unwrap(), "chatted");
+        let tokens: Vec<String> = client.generate_stream("hello").await.unwrap().try_collect().await.unwrap();
+        assert_eq!(tokens, ["streamed"]);
+
+        let models: Vec<Value> = server.requests().iter().map(|r| r["model"].clone()).collect();
+        assert_eq!(models, [json!("primary"), json!("backup"), json!("primary"), json!("backup")]);
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_node_replaces_only_failed_nodes() {
+        let server = stub_server(Duration::ZERO, replies(&["Retry | Try a smaller batch first | 0.5"])).await;
+        let client = server.client("primary");
+        let nodes = vec![create_default_node(0), create_default_node(1)];
+        let mut plan = assemble_plan(&[], "Summary".to_string(), nodes, 0.5, &PlanOptions::default());
+        plan.nodes[0].status = PlanNodeStatus::Failed;
+        plan.nodes[1].status = PlanNodeStatus::Completed;
+
+        let replacement = client.regenerate_node(&plan, plan.nodes[0].id).await.unwrap();
+        assert_eq!(replacement.id, plan.nodes[0].id);
+        assert_eq!(replacement.title, "Retry");
+
+        let refused = client.regenerate_node(&plan, plan.nodes[1].id).await;
+        assert!(matches!(
+            refused,
+            Err(OllamaError::Plan(PlanError::NodeNotFailed { status: PlanNodeStatus::Completed, .. }))
+        ));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_context_calls_stay_on_the_configured_model() {
+        let server = stub_server(Duration::ZERO, |body| match body["model"].as_str() {
+            Some("primary") => (200, json!({ "response": "next", "context": [1, 2] })),
+            _ => (404, json!({ "error": "model not found" })),
+        }).await;
+        let client = server.client("primary").with_fallback_model("backup").unwrap();
+
+        let (text, context) = client.generate_with_context("hello", None).await.unwrap();
+        assert_eq!((text.as_str(), context.as_slice()), ("next", &[1, 2][..]));
+        client.generate_with_context("again", Some(&context)).await.unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].get("context").is_none());
+        assert_eq!(requests[1]["context"], json!([1, 2]));
+
+        let server = stub_server(Duration::ZERO, |_| (404, json!({ "error": "model not found" }))).await;
+        let client = server.client("primary").with_fallback_model("backup").unwrap();
+        let failed = client.generate_with_context("hello", None).await;
+        assert!(failed.is_err_and(|e| e.is_model_unavailable()));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_stream_handles_split_chunks() {
+        let body = futures::stream::iter(vec![
+            Ok::<_, reqwest::Error>(&b"{\"response\":\"Hel"[..]),
+            Ok(&b"lo\",\"done\":false}\n{\"response\":\" world\",\"done\":false}\n"[..]),
+            Ok(&b"{\"response\":\"\",\"done\":true}"[..]),
+        ]);
+
+        let chunks: Vec<GenerateChunk> = ndjson_stream(body).try_collect().await.unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].response, "Hello");
+        assert_eq!(chunks[1].response, " world");
+        assert!(chunks[2].done);
+    }
+
+    #[test]
+    fn test_merge_node_lines_folds_continuations() {
+        let lines = [
+            "1. Research | Survey existing",
+            "collaboration frameworks | 0.2",
+            "2. Prototype | Build a first version | 0.5",
+            "focusing on the scheduler",
+            "- edge cases included",
+            "Review | Gather feedback | 0",
+        ];
+
+        assert_eq!(merge_node_lines(&lines), vec![
+            "Research | Survey existing collaboration frameworks | 0.2".to_string(),
+            "Prototype | Build a first version focusing on the scheduler - edge cases included | 0.5".to_string(),
+            "Review | Gather feedback | 0".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_thought_prompt_includes_additional_context() {
+        let context = cell_context(50.0);
+        let extra = vec!["Prefer open protocols".to_string()];
+
+        let prompt = build_thought_prompt(&PromptTemplates::default(), &context, &RealTimeContext::default(), "mission", &extra, None);
+        assert!(prompt.contains("Additional considerations:"));
+        assert!(prompt.contains("- Prefer open protocols"));
+
+        let prompt = build_thought_prompt(&PromptTemplates::default(), &context, &RealTimeContext::default(), "mission", &[], None);
+        assert!(!prompt.contains("Additional considerations:"));
+    }
+
+    #[test]
+    fn test_client_is_shareable_across_tasks() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<OllamaClient>();
+    }
+
+    #[test]
+    fn test_link_node_dependencies_drops_forward_references() {
+        let lines = [
+            "Research | Survey existing approaches | 0.0 | none",
+            "Design | Draft the architecture | 0.0 | 1",
+            "Build | Implement the design | 0.0 | 1, 2, 3, 4",
+        ];
+        let (mut nodes, deps): (Vec<_>, Vec<_>) = lines.iter()
+            .filter_map(|line| parse_plan_node(line))
+            .unzip();
+
+        link_node_dependencies(&mut nodes, &deps);
+
+        assert!(nodes[0].dependencies.is_empty());
+        assert_eq!(nodes[1].dependencies, vec![nodes[0].id]);
+        assert_eq!(nodes[2].dependencies, vec![nodes[0].id, nodes[1].id]);
+    }
+
+    #[test]
+    fn test_new_rejects_blank_model_and_trims_whitespace() {
+        assert!(matches!(OllamaClient::new("   ".to_string()), Err(OllamaError::InvalidConfig(_))));
+        assert!(matches!(OllamaClient::new("llama 3".to_string()), Err(OllamaError::InvalidConfig(_))));
+
+        let client = OllamaClient::new("  llama3.1:8b\n".to_string()).unwrap();
+        assert_eq!(client.model, "llama3.1:8b");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_returns_rendered_plan_prompt() {
+        let client = OllamaClient::new(DEFAULT_MODEL.to_string()).unwrap().with_dry_run(true);
+        let thought = Thought::from_generation("Pool compute across cells", 0.8, Vec::new());
+
+        match client.create_plan(&[thought]).await {
+            Err(OllamaError::DryRun { prompt }) => {
+                assert!(prompt.contains("- Pool compute across cells"));
+                assert!(prompt.contains("NODES:"));
+            }
+            other => panic!("expected a dry run, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drained_cell_is_refused_before_any_request() {
+        let client = OllamaClient::new(DEFAULT_MODEL.to_string()).unwrap()
+            .with_dry_run(true)
+            .with_min_thought_energy(5.0);
+        let mut context = cell_context(5.0);
+        let rtc = RealTimeContext::default();
+
+        let refused = client.generate_contextual_thought(&context, &rtc, "mission").await;
+        assert!(matches!(refused, Err(OllamaError::InsufficientEnergy { energy, required }) if energy == 5.0 && required == 5.0));
+
+        context.energy_level = 50.0;
+        let sent = client.generate_contextual_thought(&context, &rtc, "mission").await;
+        assert!(matches!(sent, Err(OllamaError::DryRun { .. })));
+
+        let none = client.think_and_plan(&context, &rtc, "mission", 0).await;
+        assert!(matches!(none, Err(OllamaError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_kmeans_separates_distinct_directions() {
+        let vectors = vec![
+            vec![1.0, 0.1],
+            vec![0.1, 1.0],
+            vec![0.9, 0.0],
+            vec![0.0, 0.8],
+            vec![1.0, 0.2],
+        ];
+        let assignments = kmeans(&vectors, 2);
+        assert_eq!(assignments[0], assignments[2]);
+        assert_eq!(assignments[0], assignments[4]);
+        assert_eq!(assignments[1], assignments[3]);
+        assert_ne!(assignments[0], assignments[1]);
+
+        assert_eq!(kmeans(&vectors, 0), vec![0; 5]);
+        assert_eq!(kmeans(&vectors[..2], 5).len(), 2);
+        assert!(kmeans(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_select_by_relevance_keeps_most_relevant_in_order() {
+        let thought = |content: &str, relevance: f64| Thought::from_generation(content, relevance, Vec::new());
+        // Each thought is estimated at 10 tokens
+        let thoughts = vec![
+            thought(&"a".repeat(40), 0.2),
+            thought(&"b".repeat(40), 0.9),
+            thought(&"c".repeat(40), 0.5),
+        ];
+
+        let selected = select_by_relevance(&thoughts, 20);
+        let contents: Vec<&str> = selected.iter().map(|t| &t.content[..1]).collect();
+        assert_eq!(contents, vec!["b", "c"]);
+
+        assert_eq!(select_by_relevance(&thoughts, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_assemble_plan_truncates_and_pads_to_node_bounds() {
+        let lines = [
+            "Research | Survey existing approaches | 0.0 | none",
+            "Design | Draft the architecture | 0.0 | 1",
+            "Build | Implement the design | 0.0 | 1, 2",
+            "Ship | Release it | 0.0 | 3",
+        ];
+        let (mut nodes, deps): (Vec<_>, Vec<_>) = lines.iter()
+            .filter_map(|line| parse_plan_node(line))
+            .unzip();
+        link_node_dependencies(&mut nodes, &deps);
+
+        let options = PlanOptions { min_nodes: 2, max_nodes: Some(2), ..Default::default() };
+        let plan = assemble_plan(&[], "Summary".to_string(), nodes.clone(), 0.5, &options);
+        assert_eq!(plan.nodes.len(), 2);
+        assert!(plan.execution_order().is_ok());
+
+        let options = PlanOptions { min_nodes: 6, ..Default::default() };
+        let plan = assemble_plan(&[], "Summary".to_string(), nodes, 0.5, &options);
+        assert_eq!(plan.nodes.len(), 6);
+    }
+
+    #[test]
+    fn test_plan_text_parser_emits_nodes_as_lines_complete() {
+        let mut parser = PlanTextParser::new(&PlanOptions { infer_dependencies: true, ..Default::default() }, &ParserConfig::default());
+        assert!(parser.push("SUMMARY:\nBuild a shelter\nNODES:\n1. Gather | Coll").is_empty());
+        assert!(parser.push("ect wood | 0.2 | none\n").is_empty());
+
+        let nodes = parser.push("2. Build | Raise the walls | 0 | 1\n");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].description, "Collect wood");
+        assert_eq!(nodes[0].estimated_completion, Score::MIN);
+
+        let nodes = parser.push("SCORE:\n0.8");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].title, "Build");
+        assert!(parser.finish().is_empty());
+
+        assert_eq!(parser.summary.as_deref(), Some("Build a shelter"));
+        assert_eq!(parser.score, Some(0.8));
+        assert_eq!(parser.nodes[1].dependencies, vec![parser.nodes[0].id]);
+    }
+
+    #[test]
+    fn test_model_unavailable_errors_are_told_apart_from_bad_requests() {
+        let server = |status, message: &str| OllamaError::Server { status, message: message.to_string() };
+
+        assert!(OllamaError::ModelNotFound("llama3.1:70b".to_string()).is_model_unavailable());
+        assert!(server(404, "model 'llama3.1:70b' not found").is_model_unavailable());
+        assert!(server(500, "model requires more system memory (40 GiB) than is available").is_model_unavailable());
+        assert!(!server(500, "unexpected EOF").is_model_unavailable());
+        assert!(!server(400, "invalid options").is_model_unavailable());
+        assert!(!OllamaError::RequestTimeout.is_model_unavailable());
+    }
+
+    #[test]
+    fn test_assemble_plan_drops_duplicate_nodes() {
+        let response = "SUMMARY:\nStudy the colony\nNODES:\n\
+            1. Initial Analysis | Review data | 0 | none\n\
+            2. Build Model | Fit a model to the data | 0 | 1\n\
+            3. initial analysis: | Review the collected data and list open questions | 0 | none\n\
+            4. Report | Share findings | 0 | 2, 3\n\
+            SCORE:\n0.7";
+        let options = PlanOptions { infer_dependencies: true, ..Default::default() };
+        let mut parser = PlanTextParser::new(&options, &ParserConfig::default());
+        parser.push(response);
+        parser.finish();
+
+        let plan = assemble_plan(&[], "Summary".to_string(), parser.nodes, 0.7, &options);
+        let analyses: Vec<_> = plan.nodes.iter()
+            .filter(|n| n.title.to_lowercase().starts_with("initial analysis"))
+            .collect();
+        assert_eq!(analyses.len(), 1);
+        assert_eq!(analyses[0].description, "Review the collected data and list open questions");
+        assert_eq!(plan.nodes.len(), 3);
+        assert!(plan.nodes[2].dependencies.contains(&analyses[0].id));
+        assert!(plan.execution_order().is_ok());
+    }
+
+    #[test]
+    fn test_json_parser_reads_plan_and_rejects_malformed_json() {
+        let response = r#"{"summary": "Map the area", "nodes": [
+            {"title": "Survey", "description": "Walk the perimeter", "estimated_completion": 0.5},
+            {"title": "Chart", "description": "Draw the map", "estimated_completion": 0.0, "depends_on": [1]}
+        ], "score": 1.4}"#;
+        let options = PlanOptions { infer_dependencies: true, ..Default::default() };
+
+        let parsed = JsonParser.parse_plan(response, &options).unwrap();
+        assert_eq!(parsed.summary.as_deref(), Some("Map the area"));
+        assert_eq!(parsed.nodes.len(), 2);
+        assert_eq!(parsed.nodes[1].dependencies, vec![parsed.nodes[0].id]);
+        assert_eq!(parsed.score, Some(1.0));
+
+        assert!(JsonParser.parse_plan("SUMMARY:\nMap the area", &options).is_err());
+    }
+
+    #[test]
+    fn test_thought_length_allows_some_slack() {
+        let sentences = ThoughtLength::Sentences { min: 2, max: 3 };
+        assert!(sentences.accepts("One. Two! Three? Four"));
+        assert!(!sentences.accepts("One. Two. Three. Four. Five. Six."));
+        assert_eq!(count_sentences("Version 2.5 ships soon... Maybe"), 2);
+
+        let characters = ThoughtLength::Characters { min: 40, max: 80 };
+        assert!(characters.accepts(&"a".repeat(32)));
+        assert!(!characters.accepts(&"a".repeat(20)));
+        assert!(!characters.accepts(&"a".repeat(120)));
+    }
+
+    #[test]
+    fn test_split_thought_ignores_headers_inside_the_text() {
+        let headers = ParserConfig::default();
+        let response = "THOUGHT: Relevance: matters more than novelty\nso rank first\nRELEVANCE: 0.8\nFACTORS:\n- focus";
+        let (thought, rest) = split_thought(response, &headers).unwrap();
+        assert_eq!(thought.trim(), "Relevance: matters more than novelty\nso rank first");
+        assert!(rest.starts_with(" 0.8\n"));
+
+        assert_eq!(split_thought("THOUGHT: idea", &headers), Err(headers.relevance.as_str()));
+        assert_eq!(split_thought("just an idea", &headers), Err(headers.thought.as_str()));
+    }
+
+    #[test]
+    fn test_clean_mission_strips_label_in_any_case() {
+        assert_eq!(clean_mission("MISSION: \"Map the commons\""), Some("Map the commons"));
+        assert_eq!(clean_mission("mission: Map the commons"), Some("Map the commons"));
+        assert_eq!(clean_mission("Missionaries map the commons"), Some("Missionaries map the commons"));
+        assert_eq!(clean_mission("Mission:  \"\""), None);
+    }
+
+    #[test]
+    fn test_match_category_accepts_only_listed_values() {
+        let categories = vec!["Research".to_string(), "Coordination".to_string()];
+        assert_eq!(match_category("  \"research\".\n", &categories).as_deref(), Some("Research"));
+        assert_eq!(match_category("- Coordination", &categories).as_deref(), Some("Coordination"));
+        assert_eq!(match_category("category: Research", &categories).as_deref(), Some("Research"));
+        assert_eq!(match_category("CATEGORY: Coordination", &categories).as_deref(), Some("Coordination"));
+        assert_eq!(match_category("Other", &categories).as_deref(), Some("other"));
+        assert_eq!(match_category("Logistics", &categories), None);
+    }
+
+    #[test]
+    fn test_parse_plan_assessment_reads_sections_in_any_case() {
+        let response = "Confidence:\n70%\nrisks:\n- Volunteers drop out\n2. Funding is late\nASSUMPTIONS:\nNone\n";
+        let assessment = parse_plan_assessment(response, &ParserConfig::default()).unwrap();
+        assert_eq!(assessment.confidence, 0.7);
+        assert_eq!(assessment.risks, vec!["Volunteers drop out", "Funding is late"]);
+        assert!(assessment.assumptions.is_empty());
+
+        let assessment = parse_plan_assessment("RISKS:\n- Weather", &ParserConfig::default()).unwrap();
+        assert_eq!(assessment.confidence, DEFAULT_SCORE);
+        assert!(parse_plan_assessment("Looks fine to me.", &ParserConfig::default()).is_err());
+
+        let inline = "CONFIDENCE: 0.8\nRisks: Supplier delays\n- Scope creep\nAssumptions: - Budget holds";
+        let assessment = parse_plan_assessment(inline, &ParserConfig::default()).unwrap();
+        assert_eq!(assessment.confidence, 0.8);
+        assert_eq!(assessment.risks, vec!["Supplier delays", "Scope creep"]);
+        assert_eq!(assessment.assumptions, vec!["Budget holds"]);
+    }
+
+    #[test]
+    fn test_builder_applies_parser_and_method_options() {
+        let config = ParserConfig { thought: "IDEA:".to_string(), ..Default::default() };
+        let client = OllamaClient::builder(DEFAULT_MODEL)
+            .response_parser(JsonParser)
+            .parser_config(config.clone())
+            .method_options(RequestKind::Scoring, GenerateOptions { num_predict: Some(4), ..Default::default() })
+            .build()
+            .unwrap();
+
+        assert_eq!(client.parser_config, config);
+        assert_eq!(client.parser.format(), ResponseFormat::Json);
+        assert_eq!(client.options_for(RequestKind::Scoring).num_predict, Some(4));
+        assert_eq!(client.options_for(RequestKind::Thought).num_predict, Some(512));
+    }
+
+    #[test]
+    fn test_requests_carry_user_agent_and_unique_ids() {
+        let client = OllamaClient::new(DEFAULT_MODEL.to_string()).unwrap();
+        let first = client.request(Method::GET, "tags").build().unwrap();
+        let second = client.request(Method::GET, "tags").build().unwrap();
+        assert_eq!(first.url().as_str(), format!("{}/tags", DEFAULT_OLLAMA_API_URL));
+        assert_ne!(first.headers()[REQUEST_ID_HEADER], second.headers()[REQUEST_ID_HEADER]);
+
+        let client = client.with_user_agent("colony-dashboard/2").unwrap();
+        let request = client.request(Method::GET, "tags").build().unwrap();
+        assert_eq!(request.headers()[reqwest::header::USER_AGENT], "colony-dashboard/2");
+        assert!(client.with_user_agent("bad\nagent").is_err());
+    }
+
+    #[test]
+    fn test_dedupe_plan_nodes_drops_dependencies_that_would_form_a_cycle() {
+        let node = |title: &str, dependencies: Vec<Uuid>| PlanNode {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            description: format!("{} description", title),
+            status: PlanNodeStatus::Pending,
+            importance: DEFAULT_NODE_IMPORTANCE,
+            estimated_completion: Score::MIN,
+            dependencies,
+        };
+        let first = node("Survey", Vec::new());
+        let second = node("Chart", vec![first.id]);
+        let duplicate = node("survey", vec![second.id]);
+
+        let nodes = dedupe_plan_nodes(vec![first.clone(), second.clone(), duplicate]);
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes[0].dependencies.is_empty());
+        assert_eq!(nodes[1].dependencies, vec![first.id]);
+
+        let plan = assemble_plan(&[], "Summary".to_string(), nodes, 0.5, &PlanOptions::default());
+        assert!(plan.validate().is_ok());
+    }
+
+    #[test]
+    fn test_raw_is_sent_at_request_level() {
+        let options = GenerateOptions { raw: true, num_predict: Some(8), ..Default::default() };
+        let request = GenerateRequest {
+            model: DEFAULT_MODEL,
+            prompt: "Q: 2+2\nA:",
+            stream: false,
+            options: Some(&options),
+            format: None,
+            keep_alive: None,
+            raw: options.raw,
+            images: &[],
+            context: None,
+            suffix: None,
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["raw"], true);
+        assert_eq!(json["options"], serde_json::json!({ "num_predict": 8 }));
+        assert!(json.get("suffix").is_none());
+    }
+
+    #[test]
+    fn test_parse_node_weights_reads_numbered_lines() {
+        let response = "1: 0.9\n- 3: 0.2\nnotes: skip this\n7: 1.0";
+        assert_eq!(parse_node_weights(response, 3), vec![Some(0.9), None, Some(0.2)]);
+    }
+}
@@ -22,9 +22,10 @@
 
 use crate::models::types::{
     CellContext, RealTimeContext, Thought, Plan, PlanNode, PlanNodeStatus, PlanStatus,
-    DimensionalPosition,
+    DimensionalPosition, Event, Score,
 };
 use crate::models::KnowledgeBase;
+use crate::api::parsing::normalize_text;
 use chrono::{DateTime, Utc};
 use rand::Rng;
 use reqwest;
@@ -270,10 +271,9 @@ impl OpenRouterClient {
         ).await??;
 
         let json: serde_json::Value = response.json().await?;
-        let response_text = json["choices"][0]["message"]["content"]
+        let response_text = normalize_text(json["choices"][0]["message"]["content"]
             .as_str()
-            .unwrap_or("")
-            .to_string();
+            .unwrap_or(""));
             
         // Parse and extract events with additional validation
         let mut events = Vec::new();
@@ -342,7 +342,7 @@ impl OpenRouterClient {
                 !previous_contexts.iter().any(|ctx| 
                     ctx.market_trends.iter().any(|t| t.to_lowercase() == topic_lower) ||
                     ctx.technological_developments.iter().any(|t| t.to_lowercase() == topic_lower) ||
-                    ctx.current_events.iter().any(|t| t.to_lowercase() == topic_lower)
+                    ctx.current_events.iter().any(|e| e.description.to_lowercase() == topic_lower)
                 )
             })
             .collect();
@@ -451,7 +451,10 @@ Recent colony thoughts:
         let mut context = RealTimeContext {
             timestamp: Utc::now(),
             market_trends: parsed.get("market_trends").cloned().unwrap_or_default(),
-            current_events: parsed.get("current_events").cloned().unwrap_or_default(),
+            current_events: parsed
+                .get("current_events")
+                .map(|events| events.iter().map(|e| Event::parse(e)).collect())
+                .unwrap_or_default(),
             technological_developments: parsed
                 .get("technological_developments")
                 .cloned()
@@ -490,7 +493,7 @@ Recent colony thoughts:
             
             clean_context.market_trends.retain(|t| seen_topics.insert(normalize_topic(t)));
             clean_context.technological_developments.retain(|t| seen_topics.insert(normalize_topic(t)));
-                            clean_context.current_events.retain(|t| seen_topics.insert(normalize_topic(t)));
+                            clean_context.current_events.retain(|e| seen_topics.insert(normalize_topic(&e.description)));
             clean_context.user_interactions.retain(|t| seen_topics.insert(normalize_topic(t)));
             
             history.contexts.push_back(clean_context.clone());
@@ -689,7 +692,8 @@ ENERGY: {}
                 real_time_context
                     .current_events
                     .first()
-                    .unwrap_or(&String::new()),
+                    .map(|e| e.description.as_str())
+                    .unwrap_or_default(),
                 real_time_context
                     .user_interactions
                     .first()
@@ -986,8 +990,9 @@ ENERGY: {}
             title: "Initial System Analysis".to_string(),
             description: "Analyze current system state and identify key improvement vectors".to_string(),
             dependencies: Vec::new(),
-            estimated_completion: 0.2,
+            estimated_completion: Score::from(0.2),
             status: PlanNodeStatus::Pending,
+            importance: crate::models::constants::DEFAULT_NODE_IMPORTANCE,
         };
         nodes.push(default_node);
 
@@ -1028,8 +1033,9 @@ ENERGY: {}
                         title,
                         description: String::new(),
                         dependencies: Vec::new(),
-                        estimated_completion: rand::thread_rng().gen_range(0.2..0.4),
+                        estimated_completion: Score::from(rand::thread_rng().gen_range(0.2..0.4)),
                         status: PlanNodeStatus::Pending,
+                        importance: crate::models::constants::DEFAULT_NODE_IMPORTANCE,
                     });
                 } else if (line.starts_with('-') || line.starts_with("DESCRIPTION:")) && current_node.is_some() {
                     if let Some(ref mut node) = current_node {
@@ -1058,15 +1064,16 @@ ENERGY: {}
                         title: line.split(':').next().unwrap_or(line).trim().to_string(),
                         description: line.split(':').nth(1).unwrap_or("").trim().to_string(),
                         dependencies: Vec::new(),
-                        estimated_completion: rand::thread_rng().gen_range(0.2..0.4),
+                        estimated_completion: Score::from(rand::thread_rng().gen_range(0.2..0.4)),
                         status: PlanNodeStatus::Pending,
+                        importance: crate::models::constants::DEFAULT_NODE_IMPORTANCE,
                     });
                 }
             }
         }
 
         let score = if !nodes.is_empty() {
-            nodes.iter().map(|n| n.estimated_completion).sum::<f64>() / nodes.len() as f64
+            nodes.iter().map(|n| n.estimated_completion.value()).sum::<f64>() / nodes.len() as f64
         } else {
             0.0
         };
@@ -1076,7 +1083,7 @@ ENERGY: {}
             thoughts: thoughts.to_vec(),
             nodes,
             summary,
-            score,
+            score: Score::from(score),
             participating_cells: Vec::new(),
             created_at: Utc::now(),
             status: PlanStatus::Proposed,
@@ -1394,10 +1401,9 @@ ENERGY: {}
 
         let json: serde_json::Value = response.json().await?;
 
-        Ok(json["choices"][0]["message"]["content"]
+        Ok(normalize_text(json["choices"][0]["message"]["content"]
             .as_str()
-            .unwrap_or("")
-            .to_string())
+            .unwrap_or("")))
     }
 
     fn parse_context_response(
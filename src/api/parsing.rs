@@ -0,0 +1,281 @@
+// MIT License
+
+/*Copyright (c) 2024 Based Labs
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
+
+// Helpers for pulling structured values out of free-form model output.
+
+use serde::de::DeserializeOwned;
+
+// Returns every signed decimal number in `text`, in order of appearance,
+// ignoring any labels or punctuation around them.
+pub fn extract_numbers(text: &str) -> Vec<f64> {
+    let bytes = text.as_bytes();
+    let mut numbers = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        if matches!(bytes[i], b'+' | b'-') {
+            i += 1;
+        }
+
+        let int_start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        let int_digits = i - int_start;
+
+        let mut frac_digits = 0;
+        if i < bytes.len() && bytes[i] == b'.' {
+            let dot = i;
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+                frac_digits += 1;
+            }
+            if frac_digits == 0 {
+                i = dot;
+            }
+        }
+
+        if int_digits + frac_digits == 0 {
+            i = start + 1;
+            continue;
+        }
+
+        if let Ok(value) = text[start..i].parse() {
+            numbers.push(value);
+        }
+    }
+
+    numbers
+}
+
+// Cleans up model output so section headers match byte-for-byte: drops BOMs
+// and zero-width characters, turns non-breaking and other Unicode spaces
+// into plain spaces, and converts CRLF, CR and Unicode line separators to \n.
+pub fn normalize_text(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{FEFF}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' => {}
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                normalized.push('\n');
+            }
+            '\u{2028}' | '\u{2029}' | '\u{0085}' => normalized.push('\n'),
+            c if c != '\n' && c != '\t' && c.is_whitespace() => normalized.push(' '),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+// Removes a markdown code fence wrapping the whole response, including an
+// optional language tag. Backticks inside the content are left alone.
+pub fn strip_code_fences(text: &str) -> &str {
+    let trimmed = text.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return text;
+    };
+
+    let body = match rest.find('\n') {
+        Some(pos) if is_language_tag(&rest[..pos]) => &rest[pos + 1..],
+        None if is_language_tag(rest) => "",
+        _ => rest,
+    };
+
+    let body = body.trim_end();
+    body.strip_suffix("```").unwrap_or(body).trim()
+}
+
+// Drops a leading bullet (`-`, `*`, `•`) or number (`1.`, `2)`) from a list
+// item. Numbers only count as markers when followed by whitespace, so values
+// like `1.5x` are left intact.
+pub fn strip_list_marker(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    if let Some(rest) = trimmed.strip_prefix(['-', '*', '•']) {
+        return rest.trim_start();
+    }
+
+    let digits = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+    if digits > 0 {
+        let rest = &trimmed[digits..];
+        if let Some(after) = rest.strip_prefix(['.', ')']) {
+            if after.is_empty() || after.starts_with(char::is_whitespace) {
+                return after.trim_start();
+            }
+        }
+    }
+
+    trimmed
+}
+
+// Reads a 0-1 fraction written either as a decimal or as a percentage
+pub fn parse_fraction(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let value = match text.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok().map(|p| p / 100.0),
+        None => text.parse().ok(),
+    };
+    value.filter(|v: &f64| v.is_finite())
+}
+
+// Parses JSON strictly, then once more after repair_json. The strict error
+// is the one returned, since it points at the model's actual mistake.
+pub fn parse_json_lenient<T: DeserializeOwned>(text: &str) -> Result<T, serde_json::Error> {
+    let text = strip_code_fences(text);
+    serde_json::from_str(text).or_else(|e| {
+        let repaired = repair_json(text);
+        match serde_json::from_str(&repaired) {
+            Ok(value) => {
+                tracing::debug!(error = %e, "parsed JSON after repairing it");
+                Ok(value)
+            }
+            Err(_) => Err(e),
+        }
+    })
+}
+
+// Fixes the small mistakes models make in otherwise valid JSON: trailing
+// commas, raw newlines and tabs inside strings, and brackets or a string
+// left open when the response was cut off. Closers with no matching opener
+// are dropped. Anything else is left for the parser to reject.
+pub fn repair_json(text: &str) -> String {
+    let mut repaired = String::with_capacity(text.len() + 8);
+    let mut open = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if in_string {
+            match c {
+                _ if escaped => {
+                    escaped = false;
+                    repaired.push(c);
+                }
+                '\\' => {
+                    escaped = true;
+                    repaired.push(c);
+                }
+                '"' => {
+                    in_string = false;
+                    repaired.push(c);
+                }
+                '\n' => repaired.push_str("\\n"),
+                '\t' => repaired.push_str("\\t"),
+                '\r' => {}
+                c => repaired.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                repaired.push(c);
+            }
+            '{' => {
+                open.push('}');
+                repaired.push(c);
+            }
+            '[' => {
+                open.push(']');
+                repaired.push(c);
+            }
+            '}' | ']' if open.contains(&c) => {
+                while let Some(closer) = open.pop() {
+                    drop_trailing_comma(&mut repaired);
+                    repaired.push(closer);
+                    if closer == c {
+                        break;
+                    }
+                }
+            }
+            '}' | ']' => {}
+            c => repaired.push(c),
+        }
+    }
+
+    if in_string {
+        repaired.push('"');
+    }
+    while let Some(closer) = open.pop() {
+        drop_trailing_comma(&mut repaired);
+        repaired.push(closer);
+    }
+    repaired
+}
+
+fn drop_trailing_comma(text: &mut String) {
+    let kept = text.trim_end().len();
+    text.truncate(kept);
+    if text.ends_with(',') {
+        text.pop();
+    }
+}
+
+fn is_language_tag(s: &str) -> bool {
+    s.trim()
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_json_fixes_trailing_commas_newlines_and_truncation() {
+        assert_eq!(repair_json(r#"{"a": [1, 2,], "b": "x",}"#), r#"{"a": [1, 2], "b": "x"}"#);
+        assert_eq!(repair_json("{\"a\": \"two\nlines\"}"), r#"{"a": "two\nlines"}"#);
+        assert_eq!(repair_json(r#"{"nodes": [{"title": "Cut off"#), r#"{"nodes": [{"title": "Cut off"}]}"#);
+        assert_eq!(repair_json(r#"{"a": "keep, ]} inside"}]"#), r#"{"a": "keep, ]} inside"}"#);
+
+        let value: serde_json::Value = parse_json_lenient("```json\n{\"score\": 0.5,}\n```").unwrap();
+        assert_eq!(value["score"], 0.5);
+        assert!(parse_json_lenient::<serde_json::Value>("SUMMARY: not json").is_err());
+    }
+
+    #[test]
+    fn test_extract_numbers_ignores_labels_and_layout() {
+        assert_eq!(extract_numbers("energy: +0.3, dopamine: -0.1"), vec![0.3, -0.1]);
+        assert_eq!(extract_numbers("0.25,\n-.5\n"), vec![0.25, -0.5]);
+        assert_eq!(extract_numbers("Energy adjustment is 2. Dopamine-wise, 1.5"), vec![2.0, 1.5]);
+        assert!(extract_numbers("no numbers - here.").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_text_makes_headers_match() {
+        let text = "\u{FEFF}SUMMARY:\u{200B}\r\nA\u{00A0}plan\rNODES:\u{2028}1. Build";
+        assert_eq!(normalize_text(text), "SUMMARY:\nA plan\nNODES:\n1. Build");
+        assert_eq!(normalize_text("plain\ttext\n"), "plain\ttext\n");
+    }
+
+    #[test]
+    fn test_strip_code_fences() {
+        assert_eq!(strip_code_fences("```\nSUMMARY:\nA plan\n```"), "SUMMARY:\nA plan");
+        assert_eq!(strip_code_fences("```text\nMARKET_TRENDS:\nAI\n```\n"), "MARKET_TRENDS:\nAI");
+        assert_eq!(strip_code_fences("SUMMARY:\nUse `cargo` here"), "SUMMARY:\nUse `cargo` here");
+        assert_eq!(strip_code_fences("```\nTHOUGHT:\nUse ``` fences sparingly\n```"), "THOUGHT:\nUse ``` fences sparingly");
+    }
+
+    #[test]
+    fn test_strip_list_marker() {
+        assert_eq!(strip_list_marker("1. Research | Gather data"), "Research | Gather data");
+        assert_eq!(strip_list_marker("  12) Build"), "Build");
+        assert_eq!(strip_list_marker("- Deploy"), "Deploy");
+        assert_eq!(strip_list_marker("* Review"), "Review");
+        assert_eq!(strip_list_marker("1.5x throughput"), "1.5x throughput");
+        assert_eq!(strip_list_marker("3D modeling"), "3D modeling");
+    }
+}
@@ -0,0 +1,142 @@
+// MIT License
+
+/*Copyright (c) 2024 Based Labs
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
+
+use crate::api::ollama::{OllamaError, PlanOptions};
+use crate::models::types::{PlanNode, RealTimeContext};
+
+// The output layouts the structured prompts can ask for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseFormat {
+    // SUMMARY: / NODES: style headers with pipe-separated node fields
+    Sections,
+    Json,
+}
+
+// What a parser could read from a plan response. The client fills in
+// defaults for anything missing, so partial results are fine.
+#[derive(Clone, Debug, Default)]
+pub struct ParsedPlan {
+    pub summary: Option<String>,
+    pub nodes: Vec<PlanNode>,
+    pub score: Option<f64>,
+}
+
+// Section headers the text parsers look for, matched case-insensitively after
+// trimming. The defaults are what the built-in prompts ask for; change them
+// for models that insist on their own labels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParserConfig {
+    pub thought: String,
+    pub relevance: String,
+    pub factors: String,
+    pub summary: String,
+    pub nodes: String,
+    pub score: String,
+    pub market_trends: String,
+    pub tech_developments: String,
+    pub current_events: String,
+    pub user_interactions: String,
+    pub environmental_data: String,
+    pub mission_progress: String,
+    pub confidence: String,
+    pub risks: String,
+    pub assumptions: String,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            thought: "THOUGHT:".to_string(),
+            relevance: "RELEVANCE:".to_string(),
+            factors: "FACTORS:".to_string(),
+            summary: "SUMMARY:".to_string(),
+            nodes: "NODES:".to_string(),
+            score: "SCORE:".to_string(),
+            market_trends: "MARKET_TRENDS:".to_string(),
+            tech_developments: "TECH_DEVELOPMENTS:".to_string(),
+            current_events: "CURRENT_EVENTS:".to_string(),
+            user_interactions: "USER_INTERACTIONS:".to_string(),
+            environmental_data: "ENVIRONMENTAL_DATA:".to_string(),
+            mission_progress: "MISSION_PROGRESS:".to_string(),
+            confidence: "CONFIDENCE:".to_string(),
+            risks: "RISKS:".to_string(),
+            assumptions: "ASSUMPTIONS:".to_string(),
+        }
+    }
+}
+
+// True when a whole line is the given header
+pub fn header_matches(line: &str, header: &str) -> bool {
+    line.trim().eq_ignore_ascii_case(header.trim())
+}
+
+// What follows the header when a line starts with it, so a header can share
+// its line with the first value
+pub fn strip_header<'a>(line: &'a str, header: &str) -> Option<&'a str> {
+    let line = line.trim_start();
+    let header = header.trim();
+    if header.is_empty() {
+        return None;
+    }
+    let prefix = line.get(..header.len())?;
+    prefix.eq_ignore_ascii_case(header).then(|| &line[header.len()..])
+}
+
+// Splits around the first line that starts with a header, returning what
+// comes before the header and what follows it. The header word elsewhere in
+// a line is ordinary text.
+pub fn split_at_header<'a>(text: &'a str, header: &str) -> Option<(&'a str, &'a str)> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if strip_header(line, header).is_some() {
+            let start = offset + line.len() - line.trim_start().len();
+            return Some((&text[..start], &text[start + header.trim().len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+// Turns model output into plans and context for the structured methods.
+// The client requests whichever format the parser reports, so a parser for
+// a custom model only needs to understand that model's layout.
+pub trait ResponseParser: Send + Sync {
+    fn format(&self) -> ResponseFormat {
+        ResponseFormat::Sections
+    }
+
+    fn parse_plan(&self, response: &str, options: &PlanOptions) -> Result<ParsedPlan, OllamaError>;
+
+    fn parse_real_time_context(&self, response: &str) -> Result<RealTimeContext, OllamaError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_match_regardless_of_case_and_padding() {
+        assert!(header_matches("  summary: ", "SUMMARY:"));
+        assert!(!header_matches("Summary: the plan", "SUMMARY:"));
+
+        let (before, after) = split_at_header("intro\nThought: idea\n", "THOUGHT:").unwrap();
+        assert_eq!(before, "intro\n");
+        assert_eq!(after, " idea\n");
+        assert!(split_at_header("nothing here", "THOUGHT:").is_none());
+
+        let text = "Weigh the relevance: of each idea\n  RELEVANCE: 0.8\n";
+        let (before, after) = split_at_header(text, "RELEVANCE:").unwrap();
+        assert_eq!(before, "Weigh the relevance: of each idea\n  ");
+        assert_eq!(after, " 0.8\n");
+
+        assert_eq!(strip_header("  Score: 0.7", "SCORE:"), Some(" 0.7"));
+        assert_eq!(strip_header("Scores vary", "SCORE:"), None);
+    }
+}
@@ -0,0 +1,51 @@
+// MIT License
+
+/*Copyright (c) 2024 Based Labs
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
+
+// Client metrics, recorded through the `metrics` facade when the "metrics"
+// feature is enabled. They are no-ops without a recorder installed, and
+// compile away entirely without the feature.
+
+use std::time::Duration;
+
+// One HTTP round trip to Ollama, labelled by endpoint
+#[cfg(feature = "metrics")]
+pub fn record_request(endpoint: &'static str, elapsed: Duration) {
+    ::metrics::counter!("ollama_requests_total", "endpoint" => endpoint).increment(1);
+    ::metrics::histogram!("ollama_request_duration_seconds", "endpoint" => endpoint).record(elapsed.as_secs_f64());
+}
+
+// Time spent in one structured call, labelled by method
+#[cfg(feature = "metrics")]
+pub fn record_method_latency(method: &'static str, elapsed: Duration) {
+    ::metrics::histogram!("ollama_method_duration_seconds", "method" => method).record(elapsed.as_secs_f64());
+}
+
+#[cfg(feature = "metrics")]
+pub fn record_retry(method: &'static str) {
+    ::metrics::counter!("ollama_retries_total", "method" => method).increment(1);
+}
+
+// The model's reply didn't have the expected structure and a fallback was used
+#[cfg(feature = "metrics")]
+pub fn record_parse_failure(method: &'static str) {
+    ::metrics::counter!("ollama_parse_failures_total", "method" => method).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn record_request(_endpoint: &'static str, _elapsed: Duration) {}
+
+#[cfg(not(feature = "metrics"))]
+pub fn record_method_latency(_method: &'static str, _elapsed: Duration) {}
+
+#[cfg(not(feature = "metrics"))]
+pub fn record_retry(_method: &'static str) {}
+
+#[cfg(not(feature = "metrics"))]
+pub fn record_parse_failure(_method: &'static str) {}
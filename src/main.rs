@@ -101,6 +101,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .value_name("MODEL")
             .help("Sets the Ollama model to use (default: llama3.1:8b)")
             .takes_value(true))
+        .arg(Arg::with_name("ollama-url")
+            .long("ollama-url")
+            .value_name("URL")
+            .help("Sets the Ollama API base URL (default: http://localhost:11434/api)")
+            .takes_value(true))
         .arg(Arg::with_name("mission")
             .short('m')
             .long("mission")
@@ -125,6 +130,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .value_name("COUNT")
             .help("Sets the initial number of cells (default: 32)")
             .takes_value(true))
+        .arg(Arg::with_name("prompts")
+            .long("prompts")
+            .value_name("DIR")
+            .help("Overrides prompt templates with <name>.txt files from DIR")
+            .takes_value(true))
         .get_matches();
 
     let initial_cells = matches.value_of("cells")
@@ -138,11 +148,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let colony_name = matches.value_of("name").unwrap_or("Unnamed");
     
     let model = matches.value_of("model")
-        .unwrap_or("llama3.1:8b")
-        .to_string();
+        .unwrap_or(api::ollama::DEFAULT_MODEL);
 
-    let api_client = api::ollama::OllamaClient::new(model)
-        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    let mut client_builder = api::ollama::OllamaClient::builder(model);
+    if let Some(base_url) = matches.value_of("ollama-url") {
+        client_builder = client_builder.base_url(base_url);
+    }
+    if let Some(dir) = matches.value_of("prompts") {
+        let templates = api::prompts::PromptTemplates::from_dir(std::path::Path::new(dir))?;
+        client_builder = client_builder.prompt_templates(templates);
+    }
+    let api_client = client_builder.build()?;
     let mut colony = Colony::new(&mission, api_client);
 
     let state_file = matches.value_of("state").unwrap_or("eca_state.json");
@@ -12,6 +12,7 @@ pub const MAX_MEMORY_SIZE: usize = 50000;
 pub const MAX_THOUGHTS_FOR_PLAN: usize = 42;
 pub const NEIGHBOR_DISTANCE_THRESHOLD: f64 = 2.0;
 pub const BATCH_SIZE: usize = 5;
+pub const DEFAULT_NODE_IMPORTANCE: f64 = 0.5;
 
 // Timing constants
 pub const CELL_INIT_DELAY_MS: u64 = 2;
@@ -13,6 +13,7 @@ pub mod constants;
 pub mod knowledge;
 pub mod plan_analysis;
 pub mod state;
+pub mod persistence;
 
 pub use types::*;
 pub use knowledge::*;
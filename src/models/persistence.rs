@@ -0,0 +1,111 @@
+// MIT License
+
+/*Copyright (c) 2024 Based Labs
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::models::types::{Plan, Thought};
+
+pub fn save_plans(path: &Path, plans: &[Plan]) -> io::Result<()> {
+    write_json_atomic(path, plans)
+}
+
+pub fn load_plans(path: &Path) -> io::Result<Vec<Plan>> {
+    read_json(path)
+}
+
+pub fn save_thoughts(path: &Path, thoughts: &[Thought]) -> io::Result<()> {
+    write_json_atomic(path, thoughts)
+}
+
+pub fn load_thoughts(path: &Path) -> io::Result<Vec<Thought>> {
+    read_json(path)
+}
+
+// Writes to a sibling temp file and renames it over the target, so a crash
+// mid-write leaves the previous checkpoint intact rather than a truncated one.
+// The file is synced before the rename and the directory after it, so the
+// new checkpoint is on disk once this returns.
+pub fn write_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    let tmp_path = temp_path_for(path);
+
+    let mut file = File::create(&tmp_path)?;
+    let written = file.write_all(json.as_bytes()).and_then(|_| file.sync_all());
+    drop(file);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+// A rename is only durable once the directory entry is
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+// Windows can't open a directory as a file, and NTFS journals the rename
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::types::{PlanStatus, Score};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_save_and_load_plans_round_trip() {
+        let dir = std::env::temp_dir().join(format!("creature-persistence-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plans.json");
+
+        let plans = vec![Plan {
+            id: Uuid::new_v4(),
+            thoughts: Vec::new(),
+            nodes: Vec::new(),
+            summary: "Checkpointed plan".to_string(),
+            score: Score::from(0.7),
+            participating_cells: vec![Uuid::new_v4()],
+            created_at: Utc::now(),
+            status: PlanStatus::Proposed,
+        }];
+
+        save_plans(&path, &plans).unwrap();
+        assert!(!temp_path_for(&path).exists());
+        assert_eq!(load_plans(&path).unwrap(), plans);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
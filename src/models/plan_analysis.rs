@@ -39,7 +39,7 @@ impl PlanAnalysis {
             .count();
             
         let avg_score = if total > 0 {
-            plans.iter().map(|p| p.score).sum::<f64>() / total as f64
+            plans.iter().map(|p| p.score.value()).sum::<f64>() / total as f64
         } else {
             0.0
         };
@@ -55,7 +55,7 @@ impl PlanAnalysis {
             failed_plans: failed,
             average_score: avg_score,
             best_plan_id: best_plan.map(|p| p.id),
-            best_plan_score: best_plan.map(|p| p.score).unwrap_or(0.0),
+            best_plan_score: best_plan.map(|p| p.score.value()).unwrap_or(0.0),
             best_plan_summary: best_plan.map(|p| p.summary.clone()).unwrap_or_default(),
         }
     }
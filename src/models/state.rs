@@ -15,6 +15,7 @@ use chrono::{DateTime, Utc};
 use uuid::Uuid;
 use std::collections::HashMap;
 use crate::models::types::{Plan, Thought, DimensionalPosition};
+use crate::models::persistence::write_json_atomic;
 
 #[derive(Serialize, Deserialize)]
 pub struct CellState {
@@ -62,9 +63,7 @@ pub struct LeniaWorldState {
 
 impl ColonyState {
     pub fn save_to_file(&self, path: &Path) -> std::io::Result<()> {
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write(path, json)?;
-        Ok(())
+        write_json_atomic(path, self)
     }
 
     pub fn load_from_file(path: &Path) -> std::io::Result<Self> {
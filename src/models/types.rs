@@ -8,22 +8,134 @@ The above copyright notice and this permission notice shall be included in all c
 
 THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::ops::{Add, Sub};
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
+use crate::models::constants::DEFAULT_NODE_IMPORTANCE;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RealTimeContext {
     pub timestamp: DateTime<Utc>,
     pub market_trends: Vec<String>,
-    pub current_events: Vec<String>,
+    pub current_events: Vec<Event>,
     pub technological_developments: Vec<String>,
     pub user_interactions: Vec<String>,
-    pub environmental_data: HashMap<String, String>,
+    pub environmental_data: HashMap<String, EnvValue>,
     pub mission_progress: Vec<String>,
 }
 
+// An environmental signal. Serialized untagged, so it reads and writes as a
+// plain JSON number, bool or string.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+}
+
+impl EnvValue {
+    // Infers the most specific type: a finite float, then true/false, then text
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        if let Ok(number) = text.parse::<f64>() {
+            if number.is_finite() {
+                return EnvValue::Number(number);
+            }
+        }
+        match text.to_lowercase().as_str() {
+            "true" => EnvValue::Bool(true),
+            "false" => EnvValue::Bool(false),
+            _ => EnvValue::Text(text.to_string()),
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            EnvValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            EnvValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            EnvValue::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for EnvValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvValue::Number(n) => write!(f, "{}", n),
+            EnvValue::Bool(b) => write!(f, "{}", b),
+            EnvValue::Text(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+// A current event, with when it happened if the source said so
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub description: String,
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+impl Event {
+    pub fn new(description: impl Into<String>) -> Self {
+        Self { description: description.into(), occurred_at: None }
+    }
+
+    // Reads an optional leading date, RFC 3339 or YYYY-MM-DD and optionally
+    // in brackets, separated from the description by a colon or dash:
+    // "2024-05-01: Launch announced", "[2024-05-01T09:00:00Z] Launch announced"
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim();
+        let (stamp, rest) = match line.strip_prefix('[').and_then(|l| l.split_once(']')) {
+            Some((stamp, rest)) => (stamp, rest),
+            None => line.split_at(line.find(char::is_whitespace).unwrap_or(line.len())),
+        };
+        let stamp = stamp.trim().trim_end_matches([':', ',']);
+
+        let occurred_at = DateTime::parse_from_rfc3339(stamp)
+            .map(|t| t.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(stamp, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|d| d.and_hms_opt(0, 0, 0))
+                    .map(|t| t.and_utc())
+            });
+
+        match occurred_at {
+            Some(occurred_at) => {
+                let description = rest.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, ':' | '-' | '–'));
+                Self { description: description.trim().to_string(), occurred_at: Some(occurred_at) }
+            }
+            None => Self::new(line),
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.occurred_at {
+            Some(occurred_at) => write!(f, "{}: {}", occurred_at.format("%Y-%m-%d"), self.description),
+            None => write!(f, "{}", self.description),
+        }
+    }
+}
+
 impl Default for RealTimeContext {
     fn default() -> Self {
         Self {
@@ -38,7 +150,46 @@ impl Default for RealTimeContext {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl RealTimeContext {
+    // Context built from real data feeds rather than the model. Sections
+    // without a feed are left empty for merge to fill in.
+    pub fn from_feeds(
+        market_trends: Vec<String>,
+        technological_developments: Vec<String>,
+        current_events: Vec<Event>,
+    ) -> Self {
+        Self {
+            market_trends,
+            technological_developments,
+            current_events,
+            ..Default::default()
+        }
+    }
+
+    // Combines factual context (self) with model-generated context. Each
+    // section the facts supply is kept as-is; empty ones are taken from the
+    // generated context. Environmental values merge per key, facts winning.
+    pub fn merge(self, generated: RealTimeContext) -> Self {
+        fn prefer<T>(facts: Vec<T>, generated: Vec<T>) -> Vec<T> {
+            if facts.is_empty() { generated } else { facts }
+        }
+
+        let mut environmental_data = generated.environmental_data;
+        environmental_data.extend(self.environmental_data);
+
+        Self {
+            timestamp: self.timestamp,
+            market_trends: prefer(self.market_trends, generated.market_trends),
+            current_events: prefer(self.current_events, generated.current_events),
+            technological_developments: prefer(self.technological_developments, generated.technological_developments),
+            user_interactions: prefer(self.user_interactions, generated.user_interactions),
+            environmental_data,
+            mission_progress: prefer(self.mission_progress, generated.mission_progress),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DimensionalPosition {
     pub emergence: f64,         // Dimension 1: -100 to 100 (Emergence vs Reduction)
     pub coherence: f64,         // Dimension 2: -100 to 100 (Coherence vs Chaos)
@@ -48,7 +199,148 @@ pub struct DimensionalPosition {
     pub integration: f64,       // Dimension 6: -100 to 100 (Integration vs Isolation)
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+// In the same order as DimensionalPosition::dimensions
+const DIMENSION_NAMES: [&str; 6] = [
+    "emergence", "coherence", "resilience", "intelligence", "efficiency", "integration",
+];
+
+// Movement smaller than this on the -100..100 scale isn't worth mentioning
+const NOTABLE_DIMENSION_CHANGE: f64 = 1.0;
+
+impl DimensionalPosition {
+    fn dimensions(&self) -> [f64; 6] {
+        [
+            self.emergence,
+            self.coherence,
+            self.resilience,
+            self.intelligence,
+            self.efficiency,
+            self.integration,
+        ]
+    }
+
+    pub fn from_dimensions(d: [f64; 6]) -> Self {
+        Self {
+            emergence: d[0],
+            coherence: d[1],
+            resilience: d[2],
+            intelligence: d[3],
+            efficiency: d[4],
+            integration: d[5],
+        }
+    }
+
+    // Euclidean distance across all six dimensions
+    pub fn distance(&self, other: &Self) -> f64 {
+        self.dimensions()
+            .iter()
+            .zip(other.dimensions())
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    // t = 0.0 gives self, t = 1.0 gives other; t is not clamped
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        let (from, to) = (self.dimensions(), other.dimensions());
+        Self::from_dimensions(std::array::from_fn(|i| from[i] + (to[i] - from[i]) * t))
+    }
+
+    pub fn magnitude(&self) -> f64 {
+        self.dimensions().iter().map(|d| d * d).sum::<f64>().sqrt()
+    }
+
+    pub fn scale(&self, factor: f64) -> Self {
+        Self::from_dimensions(self.dimensions().map(|d| d * factor))
+    }
+
+    pub fn clamp(&self, min: f64, max: f64) -> Self {
+        Self::from_dimensions(self.dimensions().map(|d| d.clamp(min, max)))
+    }
+
+    // (dimension, before, after) for each dimension that moved noticeably
+    // between self and `after`, biggest movement first
+    pub fn notable_changes(&self, after: &Self) -> Vec<(&'static str, f64, f64)> {
+        let mut changes: Vec<(&'static str, f64, f64)> = DIMENSION_NAMES.iter()
+            .zip(self.dimensions().into_iter().zip(after.dimensions()))
+            .map(|(&name, (from, to))| (name, from, to))
+            .filter(|(_, from, to)| (to - from).abs() >= NOTABLE_DIMENSION_CHANGE)
+            .collect();
+        changes.sort_by(|a, b| (b.2 - b.1).abs().total_cmp(&(a.2 - a.1).abs()));
+        changes
+    }
+
+    // Rule-based summary such as "coherence up sharply, efficiency down
+    // slightly", with no model involved
+    pub fn describe_change(&self, after: &Self) -> String {
+        let changes = self.notable_changes(after);
+        if changes.is_empty() {
+            return "no notable change".to_string();
+        }
+
+        changes.iter()
+            .map(|&(name, from, to)| {
+                let delta = to - from;
+                let direction = if delta > 0.0 { "up" } else { "down" };
+                match delta.abs() {
+                    d if d < 5.0 => format!("{} {} slightly", name, direction),
+                    d if d < 20.0 => format!("{} {}", name, direction),
+                    _ => format!("{} {} sharply", name, direction),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Add for DimensionalPosition {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (a, b) = (self.dimensions(), other.dimensions());
+        Self::from_dimensions(std::array::from_fn(|i| a[i] + b[i]))
+    }
+}
+
+impl Sub for DimensionalPosition {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        let (a, b) = (self.dimensions(), other.dimensions());
+        Self::from_dimensions(std::array::from_fn(|i| a[i] - b[i]))
+    }
+}
+
+// Weighted mean of the positions, typically weighted by cell energy. Negative
+// weights count as zero; if nothing has weight left, every position counts
+// equally. An empty slice gives the origin.
+pub fn aggregate_positions(positions: &[(DimensionalPosition, f64)]) -> DimensionalPosition {
+    let total_weight: f64 = positions.iter().map(|(_, w)| w.max(0.0)).sum();
+    let weights: Vec<f64> = if total_weight > 0.0 {
+        positions.iter().map(|(_, w)| w.max(0.0) / total_weight).collect()
+    } else {
+        vec![1.0 / positions.len().max(1) as f64; positions.len()]
+    };
+
+    let mut sum = [0.0; 6];
+    for ((position, _), weight) in positions.iter().zip(weights) {
+        for (total, value) in sum.iter_mut().zip(position.dimensions()) {
+            *total += value * weight;
+        }
+    }
+    DimensionalPosition::from_dimensions(sum)
+}
+
+// Energy and dopamine changes suggested by the model, each clamped to
+// -1.0..=1.0. The raw model text is kept for debugging.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DimensionalAdjustment {
+    pub energy: f64,
+    pub dopamine: f64,
+    pub raw_response: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CellContext {
     pub current_focus: String,
     pub active_research_topics: Vec<String>,
@@ -61,7 +353,7 @@ pub struct CellContext {
     pub dopamine: f64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Coordinates {
     // Spatial coordinates
     pub x: f64,
@@ -97,12 +389,78 @@ impl Default for Coordinates {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+// A value in 0.0..=1.0, used for plan scores, node completion and thought
+// relevance. Converting from f64 clamps (NaN becomes 0.0); Score::try_from
+// rejects out-of-range values instead. Serialized as a plain number and
+// clamped again when read back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(from = "f64", into = "f64")]
+pub struct Score(f64);
+
+impl Score {
+    pub const MIN: Score = Score(0.0);
+    pub const MAX: Score = Score(1.0);
+
+    // Shadows the blanket TryFrom that From<f64> would otherwise provide,
+    // which could never fail
+    pub fn try_from(value: f64) -> Result<Self, ScoreError> {
+        if (0.0..=1.0).contains(&value) {
+            Ok(Score(value))
+        } else {
+            Err(ScoreError { value })
+        }
+    }
+
+    pub fn value(self) -> f64 {
+        self.0
+    }
+
+    pub fn max(self, other: Score) -> Score {
+        Score(self.0.max(other.0))
+    }
+}
+
+impl From<f64> for Score {
+    fn from(value: f64) -> Self {
+        if value.is_nan() {
+            Score::MIN
+        } else {
+            Score(value.clamp(0.0, 1.0))
+        }
+    }
+}
+
+impl From<Score> for f64 {
+    fn from(score: Score) -> Self {
+        score.0
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreError {
+    pub value: f64,
+}
+
+impl std::error::Error for ScoreError {}
+
+impl std::fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Score {} is outside 0.0..=1.0", self.value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Thought {
     pub id: String,
     pub content: String,
     pub timestamp: DateTime<Utc>,
-    pub relevance_score: f64,
+    pub relevance_score: Score,
     pub context_tags: Vec<String>,
     pub real_time_factors: Vec<String>,
     pub confidence_score: f64,
@@ -110,17 +468,44 @@ pub struct Thought {
     pub referenced_thoughts: Vec<(Uuid, String)>, // (cell_id, thought_id)
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl Thought {
+    // Wraps what the thought generation methods return. Without a separate
+    // confidence estimate the relevance stands in for it; tags and
+    // references start empty for the caller to fill in.
+    pub fn from_generation(content: impl Into<String>, relevance: f64, factors: Vec<String>) -> Self {
+        let relevance_score = Score::from(relevance);
+        Thought {
+            id: Uuid::new_v4().to_string(),
+            content: content.into(),
+            timestamp: Utc::now(),
+            relevance_score,
+            context_tags: Vec::new(),
+            real_time_factors: factors,
+            confidence_score: relevance_score.value(),
+            ascii_visualization: None,
+            referenced_thoughts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlanNode {
     pub id: Uuid,
     pub title: String,
     pub description: String,
     pub dependencies: Vec<Uuid>,
-    pub estimated_completion: f64, // 0.0 to 1.0
+    pub estimated_completion: Score,
     pub status: PlanNodeStatus,
+    // How central the node is to the plan, 0.0 to 1.0
+    #[serde(default = "default_node_importance")]
+    pub importance: f64,
+}
+
+fn default_node_importance() -> f64 {
+    DEFAULT_NODE_IMPORTANCE
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PlanNodeStatus {
     Pending,
     InProgress,
@@ -129,19 +514,19 @@ pub enum PlanNodeStatus {
     Failed,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Plan {
     pub id: Uuid,
     pub thoughts: Vec<Thought>,
     pub nodes: Vec<PlanNode>,
     pub summary: String,
-    pub score: f64,
+    pub score: Score,
     pub participating_cells: Vec<Uuid>,
     pub created_at: DateTime<Utc>,
     pub status: PlanStatus,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PlanStatus {
     Proposed,
     InProgress,
@@ -149,7 +534,287 @@ pub enum PlanStatus {
     Failed,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Debug)]
+pub enum PlanError {
+    NodeNotFound(Uuid),
+    MissingDependency { node: Uuid, dependency: Uuid },
+    DependencyCycle(Vec<Uuid>),
+    DuplicateNode(Uuid),
+    NoNodes,
+    // Only failed nodes are regenerated, so progress elsewhere isn't lost
+    NodeNotFailed { node: Uuid, status: PlanNodeStatus },
+    InvalidPlanTransition { from: PlanStatus, to: PlanStatus },
+    InvalidNodeTransition { from: PlanNodeStatus, to: PlanNodeStatus },
+}
+
+impl std::error::Error for PlanError {}
+
+impl std::fmt::Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanError::NodeNotFound(id) => write!(f, "Plan node {} not found", id),
+            PlanError::MissingDependency { node, dependency } => {
+                write!(f, "Node {} depends on unknown node {}", node, dependency)
+            }
+            PlanError::DependencyCycle(nodes) => {
+                write!(f, "Dependency cycle between {} nodes", nodes.len())
+            }
+            PlanError::DuplicateNode(id) => write!(f, "Plan node {} appears more than once", id),
+            PlanError::NoNodes => write!(f, "Plan has no nodes"),
+            PlanError::NodeNotFailed { node, status } => {
+                write!(f, "Plan node {} is {:?}; only failed nodes can be regenerated", node, status)
+            }
+            PlanError::InvalidPlanTransition { from, to } => {
+                write!(f, "Plan cannot move from {:?} to {:?}", from, to)
+            }
+            PlanError::InvalidNodeTransition { from, to } => {
+                write!(f, "Plan node cannot move from {:?} to {:?}", from, to)
+            }
+        }
+    }
+}
+
+impl PlanNode {
+    pub fn mark_in_progress(&mut self) -> Result<(), PlanError> {
+        self.transition(PlanNodeStatus::InProgress, &[PlanNodeStatus::Pending, PlanNodeStatus::Blocked])
+    }
+
+    pub fn mark_complete(&mut self) -> Result<(), PlanError> {
+        self.transition(PlanNodeStatus::Completed, &[PlanNodeStatus::Pending, PlanNodeStatus::InProgress])?;
+        self.estimated_completion = Score::MAX;
+        Ok(())
+    }
+
+    pub fn mark_failed(&mut self) -> Result<(), PlanError> {
+        self.transition(
+            PlanNodeStatus::Failed,
+            &[PlanNodeStatus::Pending, PlanNodeStatus::InProgress, PlanNodeStatus::Blocked],
+        )
+    }
+
+    fn transition(&mut self, to: PlanNodeStatus, allowed_from: &[PlanNodeStatus]) -> Result<(), PlanError> {
+        if !allowed_from.contains(&self.status) {
+            return Err(PlanError::InvalidNodeTransition { from: self.status.clone(), to });
+        }
+        self.status = to;
+        Ok(())
+    }
+}
+
+impl Plan {
+    pub fn activate(&mut self) -> Result<(), PlanError> {
+        self.transition(PlanStatus::InProgress, &[PlanStatus::Proposed])
+    }
+
+    pub fn complete(&mut self) -> Result<(), PlanError> {
+        self.transition(PlanStatus::Completed, &[PlanStatus::InProgress])
+    }
+
+    pub fn abandon(&mut self) -> Result<(), PlanError> {
+        self.transition(PlanStatus::Failed, &[PlanStatus::Proposed, PlanStatus::InProgress])
+    }
+
+    pub fn set_node_completion(&mut self, node_id: Uuid, value: f64) -> Result<(), PlanError> {
+        let node = self.nodes.iter_mut()
+            .find(|n| n.id == node_id)
+            .ok_or(PlanError::NodeNotFound(node_id))?;
+        node.estimated_completion = Score::from(value);
+        Ok(())
+    }
+
+    // Most important first; equally important nodes keep their plan order
+    pub fn nodes_by_importance(&self) -> Vec<&PlanNode> {
+        let mut nodes: Vec<&PlanNode> = self.nodes.iter().collect();
+        nodes.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+        nodes
+    }
+
+    pub fn overall_completion(&self) -> f64 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        self.nodes.iter().map(|n| n.estimated_completion.value()).sum::<f64>() / self.nodes.len() as f64
+    }
+
+    fn transition(&mut self, to: PlanStatus, allowed_from: &[PlanStatus]) -> Result<(), PlanError> {
+        if !allowed_from.contains(&self.status) {
+            return Err(PlanError::InvalidPlanTransition { from: self.status.clone(), to });
+        }
+        self.status = to;
+        Ok(())
+    }
+
+    // Orders nodes so that every node comes after its dependencies, keeping the
+    // original order wherever the dependencies allow it.
+    pub fn execution_order(&self) -> Result<Vec<&PlanNode>, PlanError> {
+        let ids: HashSet<Uuid> = self.nodes.iter().map(|n| n.id).collect();
+        for node in &self.nodes {
+            if let Some(dependency) = node.dependencies.iter().find(|d| !ids.contains(d)) {
+                return Err(PlanError::MissingDependency { node: node.id, dependency: *dependency });
+            }
+        }
+
+        let mut placed = HashSet::new();
+        let mut remaining: Vec<&PlanNode> = self.nodes.iter().collect();
+        let mut ordered = Vec::with_capacity(remaining.len());
+
+        while !remaining.is_empty() {
+            let ready = remaining.iter()
+                .position(|n| n.dependencies.iter().all(|d| placed.contains(d)));
+
+            let Some(index) = ready else {
+                return Err(PlanError::DependencyCycle(remaining.iter().map(|n| n.id).collect()));
+            };
+
+            let node = remaining.remove(index);
+            placed.insert(node.id);
+            ordered.push(node);
+        }
+
+        Ok(ordered)
+    }
+
+    // Checks everything an executor relies on and reports every problem, not
+    // just the first. Unknown dependencies are reported on their own and
+    // otherwise ignored, so they don't also show up as a cycle. The score needs
+    // no check since Score clamps on construction and deserialization.
+    pub fn validate(&self) -> Result<(), Vec<PlanError>> {
+        let mut problems = Vec::new();
+
+        if self.nodes.is_empty() {
+            problems.push(PlanError::NoNodes);
+        }
+
+        let mut ids = HashSet::new();
+        for node in &self.nodes {
+            if !ids.insert(node.id) {
+                problems.push(PlanError::DuplicateNode(node.id));
+            }
+        }
+
+        for node in &self.nodes {
+            for dependency in node.dependencies.iter().filter(|d| !ids.contains(d)) {
+                problems.push(PlanError::MissingDependency { node: node.id, dependency: *dependency });
+            }
+        }
+
+        let mut placed = HashSet::new();
+        let mut remaining: Vec<&PlanNode> = self.nodes.iter().collect();
+        while let Some(index) = remaining.iter().position(|n| {
+            n.dependencies.iter().all(|d| placed.contains(d) || !ids.contains(d))
+        }) {
+            placed.insert(remaining.remove(index).id);
+        }
+        if !remaining.is_empty() {
+            problems.push(PlanError::DependencyCycle(remaining.iter().map(|n| n.id).collect()));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    // Combines two plans into a new proposed plan. Nodes whose titles match
+    // (ignoring case and spacing) collapse into this plan's node, and the
+    // other plan's dependencies are rewired to follow them. The score is
+    // averaged, weighted by how many thoughts back each plan.
+    pub fn merge(self, other: Plan) -> Plan {
+        let mut nodes = self.nodes;
+        let mut remapped: HashMap<Uuid, Uuid> = HashMap::new();
+        let mut incoming = Vec::new();
+
+        for mut node in other.nodes {
+            let key = normalize_title(&node.title);
+            match nodes.iter_mut().find(|n| normalize_title(&n.title) == key) {
+                Some(existing) => {
+                    remapped.insert(node.id, existing.id);
+                    existing.estimated_completion = existing.estimated_completion.max(node.estimated_completion);
+                    incoming.push((existing.id, node.dependencies));
+                }
+                None => {
+                    let dependencies = std::mem::take(&mut node.dependencies);
+                    incoming.push((node.id, dependencies));
+                    nodes.push(node);
+                }
+            }
+        }
+
+        // The two plans may order shared nodes differently, so an edge that
+        // would close a cycle is dropped and this plan's ordering wins
+        for (id, dependencies) in incoming {
+            let Some(index) = nodes.iter().position(|n| n.id == id) else {
+                continue;
+            };
+            for dependency in dependencies {
+                let dependency = *remapped.get(&dependency).unwrap_or(&dependency);
+                let redundant = dependency == id || nodes[index].dependencies.contains(&dependency);
+                if redundant || depends_on(&nodes, dependency, id) {
+                    continue;
+                }
+                nodes[index].dependencies.push(dependency);
+            }
+        }
+
+        let self_weight = self.thoughts.len().max(1) as f64;
+        let other_weight = other.thoughts.len().max(1) as f64;
+        let score = Score::from(
+            (self.score.value() * self_weight + other.score.value() * other_weight) / (self_weight + other_weight),
+        );
+
+        let summary = if normalize_title(&self.summary) == normalize_title(&other.summary) {
+            self.summary
+        } else {
+            format!("{} {}", self.summary.trim(), other.summary.trim())
+        };
+
+        let mut participating_cells = self.participating_cells;
+        for cell in other.participating_cells {
+            if !participating_cells.contains(&cell) {
+                participating_cells.push(cell);
+            }
+        }
+
+        let mut thoughts = self.thoughts;
+        thoughts.extend(other.thoughts);
+
+        Plan {
+            id: Uuid::new_v4(),
+            thoughts,
+            nodes,
+            summary,
+            score,
+            participating_cells,
+            created_at: Utc::now(),
+            status: PlanStatus::Proposed,
+        }
+    }
+}
+
+// Whether `from` reaches `target` by following dependencies
+fn depends_on(nodes: &[PlanNode], from: Uuid, target: Uuid) -> bool {
+    let mut pending = vec![from];
+    let mut seen = HashSet::new();
+    while let Some(id) = pending.pop() {
+        if id == target {
+            return true;
+        }
+        if !seen.insert(id) {
+            continue;
+        }
+        if let Some(node) = nodes.iter().find(|n| n.id == id) {
+            pending.extend(node.dependencies.iter().copied());
+        }
+    }
+    false
+}
+
+fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CellStatistics {
     pub thoughts_generated: u32,
     pub successful_plans: u32,
@@ -160,7 +825,7 @@ pub struct CellStatistics {
     pub average_confidence: f64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ColonyStatistics {
     pub total_cells: u32,
     pub total_thoughts: u32,
@@ -171,3 +836,310 @@ pub struct ColonyStatistics {
     pub highest_evolution_stage: u32,
     pub total_cycles: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(title: &str, dependencies: Vec<Uuid>) -> PlanNode {
+        PlanNode {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            description: format!("{} description", title),
+            dependencies,
+            estimated_completion: Score::MIN,
+            status: PlanNodeStatus::Pending,
+            importance: DEFAULT_NODE_IMPORTANCE,
+        }
+    }
+
+    fn plan(nodes: Vec<PlanNode>) -> Plan {
+        Plan {
+            id: Uuid::new_v4(),
+            thoughts: Vec::new(),
+            nodes,
+            summary: "Test plan".to_string(),
+            score: Score::from(0.5),
+            participating_cells: Vec::new(),
+            created_at: Utc::now(),
+            status: PlanStatus::Proposed,
+        }
+    }
+
+    fn position(d: [f64; 6]) -> DimensionalPosition {
+        DimensionalPosition::from_dimensions(d)
+    }
+
+    #[test]
+    fn test_dimensional_position_arithmetic_covers_every_field() {
+        let a = position([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let b = position([10.0, 20.0, 30.0, 40.0, 50.0, 60.0]);
+
+        assert_eq!((a.clone() + b.clone()).dimensions(), [11.0, 22.0, 33.0, 44.0, 55.0, 66.0]);
+        assert_eq!((b.clone() - a.clone()).dimensions(), [9.0, 18.0, 27.0, 36.0, 45.0, 54.0]);
+        assert_eq!((a.clone() - a.clone()).dimensions(), [0.0; 6]);
+        assert_eq!(a.scale(-2.0).dimensions(), [-2.0, -4.0, -6.0, -8.0, -10.0, -12.0]);
+    }
+
+    #[test]
+    fn test_env_value_parse_infers_type() {
+        assert_eq!(EnvValue::parse(" 21.5 "), EnvValue::Number(21.5));
+        assert_eq!(EnvValue::parse("True"), EnvValue::Bool(true));
+        assert_eq!(EnvValue::parse("high"), EnvValue::Text("high".to_string()));
+        assert_eq!(EnvValue::parse("NaN"), EnvValue::Text("NaN".to_string()));
+    }
+
+    #[test]
+    fn test_event_parse_reads_optional_leading_date() {
+        let dated = Event::parse("2024-05-01: Launch announced");
+        assert_eq!(dated.description, "Launch announced");
+        assert_eq!(dated.occurred_at.unwrap().to_rfc3339(), "2024-05-01T00:00:00+00:00");
+
+        let bracketed = Event::parse("[2024-05-01T09:30:00Z] Launch announced");
+        assert_eq!(bracketed.description, "Launch announced");
+        assert_eq!(bracketed.occurred_at.unwrap().to_rfc3339(), "2024-05-01T09:30:00+00:00");
+
+        assert_eq!(Event::parse("Launch announced"), Event::new("Launch announced"));
+    }
+
+    #[test]
+    fn test_aggregate_positions_weights_and_falls_back_to_plain_mean() {
+        let low = position([0.0; 6]);
+        let high = position([100.0, 50.0, -100.0, 10.0, 0.0, 20.0]);
+
+        let weighted = aggregate_positions(&[(low.clone(), 1.0), (high.clone(), 3.0)]);
+        assert_eq!(weighted.dimensions(), [75.0, 37.5, -75.0, 7.5, 0.0, 15.0]);
+
+        let unweighted = aggregate_positions(&[(low, 0.0), (high, 0.0)]);
+        assert_eq!(unweighted.dimensions(), [50.0, 25.0, -50.0, 5.0, 0.0, 10.0]);
+        assert_eq!(aggregate_positions(&[]).dimensions(), [0.0; 6]);
+    }
+
+    #[test]
+    fn test_dimensional_position_clamp_bounds_every_field() {
+        let mixed = position([-150.0, 150.0, -50.0, 50.0, -100.0, 100.0]);
+        assert_eq!(mixed.clamp(-100.0, 100.0).dimensions(), [-100.0, 100.0, -50.0, 50.0, -100.0, 100.0]);
+
+        // Each field is pushed out of range on its own so a missed field can't hide
+        for i in 0..6 {
+            let mut d = [0.0; 6];
+            d[i] = 500.0;
+            assert_eq!(position(d).clamp(-100.0, 100.0).dimensions()[i], 100.0);
+            d[i] = -500.0;
+            assert_eq!(position(d).clamp(-100.0, 100.0).dimensions()[i], -100.0);
+        }
+    }
+
+    #[test]
+    fn test_execution_order_diamond() {
+        let root = node("Root", Vec::new());
+        let left = node("Left", vec![root.id]);
+        let right = node("Right", vec![root.id]);
+        let join = node("Join", vec![left.id, right.id]);
+
+        // Deliberately out of order so the sort has work to do
+        let plan = plan(vec![join.clone(), right.clone(), left.clone(), root.clone()]);
+        let order: Vec<Uuid> = plan.execution_order().unwrap().iter().map(|n| n.id).collect();
+
+        assert_eq!(order, vec![root.id, right.id, left.id, join.id]);
+    }
+
+    #[test]
+    fn test_execution_order_detects_cycle() {
+        let mut first = node("First", Vec::new());
+        let second = node("Second", vec![first.id]);
+        first.dependencies.push(second.id);
+
+        let plan = plan(vec![first, second]);
+        assert!(matches!(plan.execution_order(), Err(PlanError::DependencyCycle(ids)) if ids.len() == 2));
+    }
+
+    #[test]
+    fn test_thought_from_generation_fills_in_the_rest() {
+        let thought = Thought::from_generation("Share compute", 1.4, vec!["demand".to_string()]);
+        assert_eq!(thought.content, "Share compute");
+        assert_eq!(thought.relevance_score, Score::MAX);
+        assert_eq!(thought.confidence_score, 1.0);
+        assert_eq!(thought.real_time_factors, vec!["demand"]);
+        assert!(Uuid::parse_str(&thought.id).is_ok());
+        assert_ne!(thought.id, Thought::from_generation("Share compute", 0.5, Vec::new()).id);
+    }
+
+    #[test]
+    fn test_validate_accepts_well_formed_plan() {
+        let root = node("Root", Vec::new());
+        let leaf = node("Leaf", vec![root.id]);
+        assert!(plan(vec![root, leaf]).validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_dangling_dependency() {
+        let missing = Uuid::new_v4();
+        let orphan = node("Orphan", vec![missing]);
+        let orphan_id = orphan.id;
+
+        let problems = plan(vec![orphan]).validate().unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            problems[0],
+            PlanError::MissingDependency { node, dependency } if node == orphan_id && dependency == missing
+        ));
+    }
+
+    #[test]
+    fn test_validate_reports_cycle() {
+        let mut first = node("First", Vec::new());
+        let second = node("Second", vec![first.id]);
+        first.dependencies.push(second.id);
+        let standalone = node("Standalone", Vec::new());
+
+        let problems = plan(vec![first, second, standalone]).validate().unwrap_err();
+        assert!(matches!(problems.as_slice(), [PlanError::DependencyCycle(ids)] if ids.len() == 2));
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let problems = plan(Vec::new()).validate().unwrap_err();
+        assert!(matches!(problems.as_slice(), [PlanError::NoNodes]));
+
+        let root = node("Root", Vec::new());
+        let orphan = node("Orphan", vec![Uuid::new_v4()]);
+        let problems = plan(vec![root.clone(), root.clone(), orphan]).validate().unwrap_err();
+        assert!(matches!(
+            problems.as_slice(),
+            [PlanError::DuplicateNode(id), PlanError::MissingDependency { .. }] if *id == root.id
+        ));
+    }
+
+    #[test]
+    fn test_merge_collapses_shared_node() {
+        let research = node("Research", Vec::new());
+        let build = node("Build", vec![research.id]);
+        let mut first = plan(vec![research.clone(), build]);
+        first.score = Score::from(0.8);
+        first.participating_cells = vec![Uuid::new_v4()];
+
+        let other_research = node("  research ", Vec::new());
+        let deploy = node("Deploy", vec![other_research.id]);
+        let mut second = plan(vec![other_research, deploy]);
+        second.score = Score::from(0.4);
+        second.participating_cells = vec![Uuid::new_v4()];
+
+        let merged = first.merge(second);
+
+        let titles: Vec<&str> = merged.nodes.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["Research", "Build", "Deploy"]);
+        assert_eq!(merged.nodes[2].dependencies, vec![research.id]);
+        assert_eq!(merged.participating_cells.len(), 2);
+        assert!((merged.score.value() - 0.6).abs() < 1e-9);
+        assert_eq!(merged.status, PlanStatus::Proposed);
+        assert!(merged.execution_order().is_ok());
+    }
+
+    #[test]
+    fn test_merge_drops_dependencies_that_would_cross() {
+        let research = node("Research", Vec::new());
+        let build = node("Build", vec![research.id]);
+        let first = plan(vec![research.clone(), build.clone()]);
+
+        let other_build = node("Build", Vec::new());
+        let other_research = node("Research", vec![other_build.id]);
+        let second = plan(vec![other_build, other_research]);
+
+        let merged = first.merge(second);
+        assert_eq!(merged.nodes.len(), 2);
+        assert!(merged.nodes[0].dependencies.is_empty());
+        assert_eq!(merged.nodes[1].dependencies, vec![research.id]);
+        assert!(merged.validate().is_ok());
+    }
+
+    #[test]
+    fn test_plan_round_trips_through_json() {
+        let research = node("Research", Vec::new());
+        let build = node("Build", vec![research.id]);
+        let mut original = plan(vec![research, build]);
+        original.status = PlanStatus::InProgress;
+        original.nodes[0].status = PlanNodeStatus::Completed;
+        original.nodes[0].estimated_completion = Score::MAX;
+        original.participating_cells = vec![Uuid::new_v4()];
+        original.thoughts.push(Thought {
+            id: Uuid::new_v4().to_string(),
+            content: "Survey existing approaches".to_string(),
+            timestamp: Utc::now(),
+            relevance_score: Score::from(0.73),
+            context_tags: vec!["research".to_string()],
+            real_time_factors: vec!["market".to_string()],
+            confidence_score: 0.61,
+            ascii_visualization: Some("[*]".to_string()),
+            referenced_thoughts: vec![(Uuid::new_v4(), Uuid::new_v4().to_string())],
+        });
+
+        let json = serde_json::to_string(&original).unwrap();
+        let restored: Plan = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn test_contexts_round_trip_through_json() {
+        let mut environment = RealTimeContext::default();
+        environment.market_trends.push("Rising demand".to_string());
+        environment.environmental_data.insert("region".to_string(), EnvValue::Text("eu".to_string()));
+        environment.environmental_data.insert("load".to_string(), EnvValue::Number(0.75));
+
+        let cell = CellContext {
+            current_focus: "Routing".to_string(),
+            active_research_topics: vec!["graphs".to_string()],
+            recent_discoveries: Vec::new(),
+            collaboration_history: Vec::new(),
+            performance_metrics: HashMap::from([("accuracy".to_string(), 0.9)]),
+            evolution_stage: 2,
+            energy_level: 87.5,
+            dimensional_position: position([1.0, -2.0, 3.0, -4.0, 5.0, -6.0]),
+            dopamine: 0.4,
+        };
+
+        let environment_json = serde_json::to_string(&environment).unwrap();
+        let cell_json = serde_json::to_string(&cell).unwrap();
+
+        assert_eq!(serde_json::from_str::<RealTimeContext>(&environment_json).unwrap(), environment);
+        assert_eq!(serde_json::from_str::<CellContext>(&cell_json).unwrap(), cell);
+    }
+
+    #[test]
+    fn test_merge_prefers_factual_sections() {
+        let mut facts = RealTimeContext::from_feeds(vec!["GPU prices falling".to_string()], Vec::new(), Vec::new());
+        facts.environmental_data.insert("load".to_string(), EnvValue::Number(0.3));
+
+        let mut generated = RealTimeContext::default();
+        generated.market_trends.push("Invented trend".to_string());
+        generated.technological_developments.push("New compiler release".to_string());
+        generated.environmental_data.insert("load".to_string(), EnvValue::Number(0.9));
+        generated.environmental_data.insert("region".to_string(), EnvValue::Text("eu".to_string()));
+
+        let merged = facts.merge(generated);
+        assert_eq!(merged.market_trends, vec!["GPU prices falling".to_string()]);
+        assert_eq!(merged.technological_developments, vec!["New compiler release".to_string()]);
+        assert_eq!(merged.environmental_data["load"], EnvValue::Number(0.3));
+        assert_eq!(merged.environmental_data["region"], EnvValue::Text("eu".to_string()));
+    }
+
+    #[test]
+    fn test_score_clamps_or_rejects_out_of_range_values() {
+        assert_eq!(Score::from(7.0), Score::MAX);
+        assert_eq!(Score::from(-0.5), Score::MIN);
+        assert_eq!(Score::from(f64::NAN), Score::MIN);
+        assert_eq!(Score::try_from(0.25).map(Score::value), Ok(0.25));
+        assert_eq!(Score::try_from(7.0), Err(ScoreError { value: 7.0 }));
+        assert_eq!(serde_json::from_str::<Score>("3.5").unwrap(), Score::MAX);
+    }
+
+    #[test]
+    fn test_describe_change_orders_by_size_and_skips_small_moves() {
+        let before = position([0.0, 10.0, 0.0, 0.0, 40.0, 0.0]);
+        let after = position([0.5, 45.0, 0.0, 0.0, 37.0, 0.0]);
+
+        assert_eq!(before.describe_change(&after), "coherence up sharply, efficiency down slightly");
+        assert_eq!(before.describe_change(&before), "no notable change");
+    }
+}
@@ -8,10 +8,10 @@ The above copyright notice and this permission notice shall be included in all c
 
 THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
 
-use crate::models::types::{CellContext, Coordinates, DimensionalPosition, Plan, RealTimeContext, Thought};
+use crate::models::types::{CellContext, Coordinates, DimensionalPosition, Plan, RealTimeContext, Score, Thought};
 use crate::models::thought_io::{EventInput, EventOutput, ThoughtIO};
 use crate::models::constants::MAX_MEMORY_SIZE;
-use crate::api::ollama::OllamaClient;
+use crate::api::language_model::LanguageModel;
 use crate::systems::ltl::{ExtendedNeighborhood, EnhancedCellState, InteractionEffect};
 use std::collections::{HashMap, VecDeque};
 use std::error::Error;
@@ -86,7 +86,7 @@ impl Cell {
 
     pub async fn update_with_ltl_rules(
         &mut self, 
-        api_client: &OllamaClient,
+        api_client: &impl LanguageModel,
         other_cells: &[(Uuid, Coordinates)]
     ) -> Result<(), Box<dyn Error>> {
         self.neighborhood.update_neighbors(&self.position, other_cells);
@@ -168,7 +168,7 @@ impl Cell {
 
     pub async fn generate_thought(
         &mut self,
-        api_client: &OllamaClient,
+        api_client: &impl LanguageModel,
         mission: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         // First evaluate dimensional state
@@ -178,13 +178,13 @@ impl Cell {
             .take(5)
             .collect();
             
-        let (energy_impact, dopamine_impact) = api_client
+        let adjustment = api_client
             .evaluate_dimensional_state(&self.dimensional_position, &recent_thoughts, &recent_plans)
             .await?;
             
         // Apply impacts
-        self.energy = (self.energy + energy_impact).clamp(0.0, 100.0);
-        self.dopamine = (self.dopamine + (dopamine_impact - 0.5)).clamp(0.0, 1.0);
+        self.energy = (self.energy + adjustment.energy).clamp(0.0, 100.0);
+        self.dopamine = (self.dopamine + (adjustment.dopamine - 0.5)).clamp(0.0, 1.0);
         let cell_context = CellContext {
             current_focus: self.get_current_focus(),
             active_research_topics: self.get_active_research(),
@@ -327,7 +327,7 @@ impl Cell {
             id: thought_id,
             content: filtered_content,
             timestamp: Utc::now(),
-            relevance_score,
+            relevance_score: Score::from(relevance_score),
             context_tags: self.generate_context_tags(&cell_context),
             real_time_factors: factors,
             confidence_score: self.calculate_confidence_score(&real_time_context),
@@ -371,7 +371,7 @@ Generated Thought:");
         Ok(())
     }
 
-    pub async fn check_and_compress_memories(&mut self, api_client: &OllamaClient) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn check_and_compress_memories(&mut self, api_client: &impl LanguageModel) -> Result<(), Box<dyn std::error::Error>> {
         let total_size: usize = self.thoughts.iter().map(|t| t.content.len()).sum();
 
         if total_size > MAX_MEMORY_SIZE {
@@ -405,7 +405,7 @@ Generated Thought:");
     pub fn get_recent_discoveries(&self) -> Vec<String> {
         self.thoughts
             .iter()
-            .filter(|t| t.relevance_score > 0.8)
+            .filter(|t| t.relevance_score.value() > 0.8)
             .take(3)
             .map(|t| t.content.clone())
             .collect()
@@ -458,9 +458,9 @@ Generated Thought:");
                     println!("║     Title: {}", node.title);
                     println!("║     Status: {:?}", node.status);
                     println!("║     Progress: [{}{}] {:.1}%",
-                        "█".repeat((node.estimated_completion * 20.0) as usize),
-                        "░".repeat(20 - (node.estimated_completion * 20.0) as usize),
-                        node.estimated_completion * 100.0
+                        "█".repeat((node.estimated_completion.value() * 20.0) as usize),
+                        "░".repeat(20 - (node.estimated_completion.value() * 20.0) as usize),
+                        node.estimated_completion.value() * 100.0
                     );
                     println!("║");
                     println!("║     Description:");
@@ -551,7 +551,7 @@ Generated Thought:");
         
         // Check current events alignment
         for event in &context.current_events {
-            if current_focus.contains(&event.to_lowercase()) {
+            if current_focus.contains(&event.description.to_lowercase()) {
                 context_relevance += 0.2;
             }
         }
@@ -8,28 +8,29 @@ The above copyright notice and this permission notice shall be included in all c
 
 THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.*/
 
-use crate::models::types::{CellContext, Coordinates, Plan, PlanStatus, ColonyStatistics, Thought, DimensionalPosition};
+use crate::models::types::{CellContext, Coordinates, Plan, PlanStatus, ColonyStatistics, Thought, DimensionalPosition, Score};
 use crate::utils::logging::*;
 use std::error::Error;
 use std::path::Path;
 use std::collections::VecDeque;
 use crate::models::plan_analysis::{PlanAnalysis, save_plan_to_file};
 use crate::models::constants::{MAX_THOUGHTS_FOR_PLAN, NEIGHBOR_DISTANCE_THRESHOLD, BATCH_SIZE};
-use crate::api::ollama::OllamaClient;
+use crate::api::language_model::LanguageModel;
+use crate::api::ollama::{BatchOptions, OllamaClient};
 use crate::systems::cell::Cell;
 use std::collections::HashMap;
 use chrono::Utc;
 use uuid::Uuid;
 use rand::{Rng, seq::SliceRandom};
 
-pub struct Colony {
+pub struct Colony<M = OllamaClient> {
     pub cells: HashMap<Uuid, Cell>,
     pub mission: String,
-    pub api_client: OllamaClient,
+    pub api_client: M,
     pub cell_positions: HashMap<Uuid, Coordinates>,
     plan_leaderboard: HashMap<Uuid, (usize, usize)>, // (thought_count, unique_collaborations)
 }
-impl Colony {
+impl<M: LanguageModel> Colony<M> {
 
     pub async fn process_cell_sub_batch(&mut self, cell_ids: &[Uuid]) -> Result<(), Box<dyn Error>> {
         let thoughts: Vec<_> = cell_ids.iter()
@@ -105,7 +106,7 @@ impl Colony {
     }
 
 
-    pub fn new(mission: &str, api_client: OllamaClient) -> Self {
+    pub fn new(mission: &str, api_client: M) -> Self {
         Self {
             cells: HashMap::new(),
             mission: mission.to_string(),
@@ -326,22 +327,39 @@ impl Colony {
         
         println!("║ [{}] Generating thoughts...", 
             chrono::Local::now().format("%H:%M:%S"));
-        let batch_results = match tokio::time::timeout(
-            std::time::Duration::from_secs(300), // Reduced timeout
-            self.api_client.generate_contextual_thoughts_batch(&cell_context_refs, &real_time_context, &self.mission, &[])
+        // A stuck cell is recorded as an error after its own limit, while the
+        // overall limit keeps a large batch queued behind the client's
+        // in-flight cap from stalling the whole cycle
+        let batch_options = BatchOptions {
+            cell_timeout: Some(std::time::Duration::from_secs(120)),
+            ..BatchOptions::default()
+        };
+        let batch = match tokio::time::timeout(
+            std::time::Duration::from_secs(300),
+            self.api_client.generate_contextual_thoughts_batch(
+                &cell_context_refs,
+                &real_time_context,
+                &self.mission,
+                &[],
+                &batch_options,
+            )
         ).await {
-            Ok(result) => match result {
-                Ok(batch) => batch,
-                Err(e) => {
-                    eprintln!("Error generating thoughts: {}", e);
-                    HashMap::new() // Return empty results on error
-                }
-            },
+            Ok(batch) => batch,
             Err(_) => {
                 eprintln!("Thought generation timed out after 300 seconds");
-                HashMap::new()
+                Vec::new()
             }
         };
+        let batch_results: Vec<_> = batch
+            .into_iter()
+            .filter_map(|(cell_id, outcome)| match outcome {
+                Ok(thoughts) => Some((cell_id, thoughts)),
+                Err(e) => {
+                    eprintln!("Error generating thoughts for cell {}: {}", cell_id, e);
+                    None
+                }
+            })
+            .collect();
         
         println!("║   Thoughts Generated: {}", batch_results.len());
         println!("║   Success Rate: {:.1}%", 
@@ -361,10 +379,6 @@ impl Colony {
                 
                 for (thought_content, relevance_score, real_time_factors) in thoughts {
                     let thought = Thought {
-                        id: Uuid::new_v4().to_string(),
-                        content: thought_content.clone(),
-                        timestamp: Utc::now(),
-                        relevance_score,
                         context_tags: updated_cell.generate_context_tags(&CellContext {
                             current_focus: updated_cell.get_current_focus(),
                             active_research_topics: updated_cell.get_active_research(),
@@ -376,10 +390,8 @@ impl Colony {
                             dimensional_position: updated_cell.dimensional_position.clone(),
                             dopamine: updated_cell.dopamine,
                         }),
-                        real_time_factors,
                         confidence_score: updated_cell.calculate_confidence_score(&real_time_context),
-                        ascii_visualization: None,
-                        referenced_thoughts: Vec::new(),
+                        ..Thought::from_generation(thought_content.clone(), relevance_score, real_time_factors)
                     };
 
                     // Parse dimensional positions from thought content
@@ -486,7 +498,7 @@ impl Colony {
                                     &cell.dimensional_position,
                                     &neighbor.dimensional_position
                                 );
-                                (thought.clone(), thought.relevance_score * dimensional_weight)
+                                (thought.clone(), thought.relevance_score.value() * dimensional_weight)
                             })
                             .collect();
                         
@@ -540,7 +552,7 @@ impl Colony {
                 
                 // Log the full plan details
                 
-                let plan_score = combined_thoughts.iter().map(|t| t.relevance_score).sum::<f64>() / combined_thoughts.len() as f64;
+                let plan_score = combined_thoughts.iter().map(|t| t.relevance_score.value()).sum::<f64>() / combined_thoughts.len() as f64;
                 
                 let plan = Plan {
                     id: Uuid::new_v4(),
@@ -556,7 +568,7 @@ impl Colony {
                     } else {
                         plan_result.summary.clone()
                     },
-                    score: plan_score,
+                    score: Score::from(plan_score),
                     participating_cells: std::iter::once(cell_id)
                         .chain(cell.neighbors.iter().cloned())
                         .collect(),
@@ -611,7 +623,7 @@ Analyzing technical context for best plan (score: {:.2})...", best_plan_score);
                 best_plan_narrative
             );
 
-            match self.api_client.query_llm(&news_query).await {
+            match self.api_client.generate(&news_query).await {
                 Ok(news) => println!("
 Relevant developments:
 {}", news),
@@ -1217,7 +1229,7 @@ Relevant developments:
             if let Some(plan) = &cell.current_plan {
                 // Calculate plan execution metrics
                 let completed_nodes = plan.nodes.iter()
-                    .filter(|n| n.estimated_completion > 0.8)
+                    .filter(|n| n.estimated_completion.value() > 0.8)
                     .count() as f64;
                 let total_nodes = plan.nodes.len() as f64;
                 let execution_rate = if total_nodes > 0.0 {